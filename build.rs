@@ -1,45 +1,322 @@
-use std::fs::create_dir_all;
-use std::path::Path;
+use std::env;
+use std::fmt::Write;
+use std::fs::{create_dir_all, read_dir, read_to_string, write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use serde_json::Value;
+
+#[path = "src/application/pipelines/slangc_flags.rs"]
+mod slangc_flags;
+
+use slangc_flags::SLANGC_FLAGS;
+
+/// Removes the `//` and `/* */` comments, a comment becomes a space
+fn strip_comments(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    let mut rest = source;
+    loop {
+        let line_comment = rest.find("//");
+        let block_comment = rest.find("/*");
+        let (start, end) = match (line_comment, block_comment) {
+            // the line comment ends before its newline, which is kept
+            (Some(line), block) if block.is_none_or(|block| line < block) => (
+                line,
+                rest[line..].find('\n').map_or(rest.len(), |end| line + end),
+            ),
+            (_, Some(block)) => (
+                block,
+                rest[block + 2..]
+                    .find("*/")
+                    .map_or(rest.len(), |end| block + 2 + end + 2),
+            ),
+            (_, None) => break,
+        };
+        stripped.push_str(&rest[..start]);
+        stripped.push(' ');
+        rest = &rest[end..];
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Finds the functions marked with a `[shader("...")]` attribute
+/// The other attributes between the marker and the function are skipped
+fn find_entry_points(source: &str) -> Vec<String> {
+    // the commented out entry points are ignored
+    let source = strip_comments(source);
+
+    let mut entry_points = Vec::new();
+    let mut rest = source.as_str();
+    while let Some(position) = rest.find("[shader(") {
+        rest = &rest[position..];
+        // skip the attributes, they may contain parentheses
+        while rest.starts_with('[') {
+            let mut depth = 0;
+            let end = rest
+                .char_indices()
+                .find(|&(_, c)| {
+                    match c {
+                        '[' => depth += 1,
+                        ']' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .map(|(index, _)| index + 1)
+                .unwrap_or(rest.len());
+            rest = rest[end..].trim_start();
+        }
+        // the entry point name is the last word before the parameters
+        let declaration = &rest[..rest.find('(').unwrap_or(rest.len())];
+        if let Some(name) = declaration.split_whitespace().last() {
+            entry_points.push(name.to_owned());
+        }
+    }
+    entry_points
+}
+
+/// Descriptor of a shader parameter, read from the reflection
+struct ShaderBinding {
+    name: String,
+    set: u64,
+    binding: u64,
+    descriptor_type: &'static str,
+    /// 0 for the arrays without size
+    descriptor_count: u64,
+}
+
+/// `_TlasModelIndices` becomes `TLAS_MODEL_INDICES`
+fn get_constant_name(parameter_name: &str) -> String {
+    let mut constant_name = String::new();
+    for (i, c) in parameter_name.trim_start_matches('_').chars().enumerate() {
+        if c.is_uppercase() && i > 0 && !constant_name.ends_with('_') {
+            constant_name.push('_');
+        }
+        constant_name.push(c.to_ascii_uppercase());
+    }
+    constant_name
+}
+
+/// `_TlasModelIndices` becomes `TlasModelIndices`
+fn get_struct_name(parameter_name: &str) -> String {
+    let mut chars = parameter_name.trim_start_matches('_').chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// Vulkan descriptor type and count of a parameter type of the reflection
+fn get_descriptor_type(parameter_name: &str, parameter_type: &Value) -> (&'static str, u64) {
+    let kind = parameter_type["kind"].as_str().unwrap_or_default();
+    let base_shape = parameter_type["baseShape"].as_str().unwrap_or_default();
+    let is_read_write = parameter_type["access"] == "readWrite";
+
+    let descriptor_type = match kind {
+        "array" => {
+            let (descriptor_type, _) =
+                get_descriptor_type(parameter_name, &parameter_type["elementType"]);
+            let count = parameter_type["elementCount"].as_u64().unwrap_or(0);
+            return (descriptor_type, count);
+        }
+        "constantBuffer" => "UNIFORM_BUFFER",
+        "samplerState" => "SAMPLER",
+        "resource" => match base_shape {
+            "structuredBuffer" | "byteAddressBuffer" => "STORAGE_BUFFER",
+            "accelerationStructure" => "ACCELERATION_STRUCTURE_KHR",
+            "textureBuffer" if is_read_write => "STORAGE_TEXEL_BUFFER",
+            "textureBuffer" => "UNIFORM_TEXEL_BUFFER",
+            _ if base_shape.starts_with("texture") && is_read_write => "STORAGE_IMAGE",
+            _ if base_shape.starts_with("texture") && parameter_type["combined"] == true => {
+                "COMBINED_IMAGE_SAMPLER"
+            }
+            _ if base_shape.starts_with("texture") => "SAMPLED_IMAGE",
+            _ => panic!("Unsupported resource {} for {}", base_shape, parameter_name),
+        },
+        _ => panic!("Unsupported parameter kind {} for {}", kind, parameter_name),
+    };
+    (descriptor_type, 1)
+}
+
+/// Lists the parameters of the reflection bound to descriptors, the push constants are skipped
+fn get_shader_bindings(reflection: &Value) -> Vec<ShaderBinding> {
+    let parameters = reflection["parameters"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    parameters
+        .iter()
+        .filter_map(|parameter| {
+            let name = parameter["name"].as_str()?;
+            let bindings = match (&parameter["binding"], &parameter["bindings"]) {
+                (binding @ Value::Object(_), _) => vec![binding.clone()],
+                (_, Value::Array(bindings)) => bindings.clone(),
+                _ => Vec::new(),
+            };
+            let binding = bindings
+                .into_iter()
+                .find(|binding| binding["kind"] == "descriptorTableSlot")?;
+            let (descriptor_type, descriptor_count) = get_descriptor_type(name, &parameter["type"]);
+            Some(ShaderBinding {
+                name: name.to_owned(),
+                set: binding["space"].as_u64().unwrap_or(0),
+                binding: binding["index"].as_u64().unwrap_or(0),
+                descriptor_type,
+                descriptor_count,
+            })
+        })
+        .collect()
+}
+
+/// Rust module with the descriptor constants of a shader and a structure per parameter
+fn write_shader_bindings_module(output: &mut String, file: &str, bindings: &[ShaderBinding]) {
+    let module_name = Path::new(file).file_stem().unwrap().to_string_lossy();
+    writeln!(output, "/// Descriptors of `{}`", file).unwrap();
+    writeln!(output, "pub mod {} {{", module_name).unwrap();
+    writeln!(
+        output,
+        "    use super::{{DescriptorType, ShaderBinding}};
+"
+    )
+    .unwrap();
+    for binding in bindings {
+        let constant_name = get_constant_name(&binding.name);
+        writeln!(
+            output,
+            "    pub const DESCRIPTOR_SET_{constant_name}: u32 = {set};
+    pub const DESCRIPTOR_BINDING_{constant_name}: u32 = {binding};
+
+    /// The parameter `{name}`
+    pub struct {struct_name};
+
+    impl {struct_name} {{
+        pub const NAME: &'static str = {name:?};
+        pub const SET: u32 = DESCRIPTOR_SET_{constant_name};
+        pub const BINDING: u32 = DESCRIPTOR_BINDING_{constant_name};
+        pub const DESCRIPTOR_TYPE: DescriptorType = DescriptorType::{descriptor_type};
+        /// 0 for the arrays declared without a size
+        pub const DESCRIPTOR_COUNT: u32 = {descriptor_count};
+    }}
+",
+            name = binding.name,
+            struct_name = get_struct_name(&binding.name),
+            set = binding.set,
+            binding = binding.binding,
+            descriptor_type = binding.descriptor_type,
+            descriptor_count = binding.descriptor_count,
+        )
+        .unwrap();
+    }
+    writeln!(output, "    pub const BINDINGS: &[ShaderBinding] = &[").unwrap();
+    for binding in bindings {
+        writeln!(
+            output,
+            "        ShaderBinding {{ name: {0}::NAME, set: {0}::SET, binding: {0}::BINDING, descriptor_type: {0}::DESCRIPTOR_TYPE, descriptor_count: {0}::DESCRIPTOR_COUNT }},",
+            get_struct_name(&binding.name)
+        )
+        .unwrap();
+    }
+    writeln!(
+        output,
+        "    ];
+}}
+"
+    )
+    .unwrap();
+}
+
+/// Compiles an entry point with `slangc`, next to the SPIR-V its reflection is written as json
+fn compile_shader(shader: &Path, entry_point: &str, output_path: &Path) {
+    // Ensure the output directory exists
+    if let Some(parent) = output_path.parent() {
+        create_dir_all(parent).unwrap();
+    }
+
+    let status = Command::new("slangc")
+        .arg(shader)
+        .args(SLANGC_FLAGS)
+        .arg("-o")
+        .arg(output_path)
+        .arg("-entry")
+        .arg(entry_point)
+        // the layout tests compare the Rust structures with the reflection
+        .arg("-reflection-json")
+        .arg(output_path.with_extension("json"))
+        .status()
+        .unwrap();
+
+    if !status.success() {
+        panic!(
+            "Shader compilation failed for {} ({})",
+            shader.display(),
+            entry_point
+        );
+    }
+}
+
 fn main() {
-    // Specify the list of shaders and their entry points
-    let shaders = vec![("src/shaders/raytracing.slang", "main")];
+    // Every top-level shader is compiled, the subdirectories contain the imported modules
+    let shader_dir = Path::new("src/shaders");
+    let mut shaders: Vec<PathBuf> = read_dir(shader_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "slang"))
+        .collect();
+    shaders.sort();
 
     // Define the base output directory
     let out_dir = Path::new("target/shaders");
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
 
-    for (shader, entry_point) in shaders {
-        // Get the output file path
-        let shader_path = Path::new(shader);
-        let relative_path = shader_path.strip_prefix("src/shaders").unwrap();
-        let output_path = out_dir.join(relative_path).with_extension("spv");
+    // Rust source embedding the compiled shaders in the binary
+    let mut shader_manifest = String::from("pub const SHADER_MANIFEST: &[CompiledShader] = &[\n");
+    // Rust modules with the descriptors declared by every shader
+    let mut shader_bindings = String::new();
 
-        // Ensure the output directory exists
-        if let Some(parent) = output_path.parent() {
-            create_dir_all(parent).unwrap();
-        }
+    for shader in shaders {
+        let file = shader.strip_prefix(shader_dir).unwrap().to_string_lossy();
+        let entry_points = find_entry_points(&read_to_string(&shader).unwrap());
+        // the entry points of a file share its global parameters
+        let mut bindings: Vec<ShaderBinding> = Vec::new();
+
+        for entry_point in entry_points {
+            // one SPIR-V per entry point: `<shader name>/<entry point>.spv`
+            let output_path = out_dir
+                .join(shader.file_stem().unwrap())
+                .join(&entry_point)
+                .with_extension("spv");
 
-        let status = Command::new("slangc")
-            .arg(shader)
-            .arg("-emit-spirv-directly")
-            .arg("-g2")
-            .arg("-profile")
-            .arg("glsl_460")
-            .arg("-target")
-            .arg("spirv")
-            .arg("-o")
-            .arg(&output_path)
-            .arg("-entry")
-            .arg(entry_point)
-            .status()
+            compile_shader(&shader, &entry_point, &output_path);
+
+            writeln!(
+                shader_manifest,
+                "    CompiledShader {{ file: {:?}, entry_point: {:?}, spv: include_bytes!({:?}) }},",
+                file,
+                entry_point,
+                Path::new(&crate_dir).join(&output_path)
+            )
             .unwrap();
 
-        if !status.success() {
-            panic!("Shader compilation failed for {}", shader);
+            let reflection: Value =
+                serde_json::from_str(&read_to_string(output_path.with_extension("json")).unwrap())
+                    .unwrap();
+            for binding in get_shader_bindings(&reflection) {
+                if !bindings.iter().any(|known| known.name == binding.name) {
+                    bindings.push(binding);
+                }
+            }
         }
+        write_shader_bindings_module(&mut shader_bindings, &file, &bindings);
     }
+    shader_manifest.push_str("];\n");
+
+    let shader_manifest_path = Path::new(&env::var("OUT_DIR").unwrap()).join("shader_manifest.rs");
+    write(shader_manifest_path, shader_manifest).unwrap();
+    let shader_bindings_path = Path::new(&env::var("OUT_DIR").unwrap()).join("shader_bindings.rs");
+    write(shader_bindings_path, shader_bindings).unwrap();
 
     // rerun when shaders change
     println!("cargo:rerun-if-changed=src/shaders");
+    println!("cargo:rerun-if-changed=src/application/pipelines/slangc_flags.rs");
 }
@@ -0,0 +1,111 @@
+//! `#[derive(GpuLayout)]`, see the `gpu-layout` crate
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields};
+
+/// Implements `GpuLayout` for a `#[repr(C)]` structure with named fields
+/// The std430 offset of every field and the size of the structure are checked at compile time
+/// The padding must be declared as fields, the bytes uploaded to the shaders are all initialized
+#[proc_macro_derive(GpuLayout)]
+pub fn derive_gpu_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "GpuLayout cannot be derived for generic types",
+        ));
+    }
+
+    // the field order of the other representations is not defined
+    let mut is_repr_c = false;
+    for attribute in input.attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attribute.parse_nested_meta(|meta| {
+            is_repr_c |= meta.path.is_ident("C");
+            Ok(())
+        })?;
+    }
+    if !is_repr_c {
+        return Err(Error::new_spanned(
+            name,
+            "GpuLayout requires the structure to be #[repr(C)]",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    name,
+                    "GpuLayout requires named fields, like the shader structures",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                name,
+                "GpuLayout can only be derived for structures",
+            ))
+        }
+    };
+
+    let field_names: Vec<_> = fields.iter().map(|field| field.ident.as_ref().unwrap()).collect();
+    let field_types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let field_strings: Vec<_> = field_names.iter().map(|ident| ident.to_string()).collect();
+
+    let field_checks = field_names.iter().zip(&field_types).map(|(field, ty)| {
+        let message = format!(
+            "`{}::{}` is not at its std430 offset right after the previous field, reorder the fields or add `_pad` fields before it",
+            name, field
+        );
+        // the field starts where the previous one ends, without implicit padding
+        quote! {
+            let offset = ::gpu_layout::align_to(end, <#ty as ::gpu_layout::GpuLayout>::ALIGNMENT);
+            assert!(
+                offset == end && offset == ::core::mem::offset_of!(#name, #field),
+                #message
+            );
+            let end = offset + <#ty as ::gpu_layout::GpuLayout>::SIZE;
+        }
+    });
+    let size_message = format!(
+        "the size of `{}` is not a multiple of its std430 alignment ending with its last field, declare its tail padding as `_pad` fields",
+        name
+    );
+
+    Ok(quote! {
+        unsafe impl ::gpu_layout::GpuLayout for #name {
+            const ALIGNMENT: usize = ::gpu_layout::max_alignment(&[
+                #(<#field_types as ::gpu_layout::GpuLayout>::ALIGNMENT),*
+            ]);
+            const FIELDS: &'static [::gpu_layout::GpuField] = &[
+                #(::gpu_layout::GpuField {
+                    name: #field_strings,
+                    offset: ::core::mem::offset_of!(#name, #field_names),
+                    size: <#field_types as ::gpu_layout::GpuLayout>::SIZE,
+                }),*
+            ];
+        }
+
+        const _: () = {
+            let end = 0usize;
+            #(#field_checks)*
+            // the implicit padding of a repr(C) structure is not initialized
+            assert!(
+                end == ::core::mem::size_of::<#name>()
+                    && end % <#name as ::gpu_layout::GpuLayout>::ALIGNMENT == 0,
+                #size_message
+            );
+        };
+    })
+}
@@ -0,0 +1,184 @@
+//! Memory layout of the structures shared with the shaders
+//!
+//! The shaders read the storage buffers with the std430 rules: every field is placed at
+//! the next multiple of its alignment and the size of a structure is a multiple of its
+//! largest alignment. `#[derive(GpuLayout)]` checks at compile time that a `#[repr(C)]`
+//! structure follows them, so a field in the wrong place does not build.
+//!
+//! The structures are copied byte by byte, so their padding is declared as `_pad` fields
+//! and initialized like the other fields:
+//!
+//! ```
+//! use glam::{Mat4, Vec4};
+//! use gpu_layout::GpuLayout;
+//!
+//! #[repr(C)]
+//! #[derive(Clone, Copy, GpuLayout)]
+//! struct Instance {
+//!     matrix: Mat4,
+//!     color: Vec4,
+//!     index: u32,
+//!     _pad0: u32,
+//!     _pad1: u32,
+//!     _pad2: u32,
+//! }
+//!
+//! assert_eq!(Instance::SIZE, 96);
+//! ```
+//!
+//! The implicit padding at the end of a structure does not build:
+//!
+//! ```compile_fail
+//! use glam::{Mat4, Vec4};
+//! use gpu_layout::GpuLayout;
+//!
+//! #[repr(C)]
+//! #[derive(Clone, Copy, GpuLayout)]
+//! struct Instance {
+//!     matrix: Mat4,
+//!     color: Vec4,
+//!     index: u32,
+//! }
+//! ```
+//!
+//! Nor does the implicit padding between two fields:
+//!
+//! ```compile_fail
+//! use glam::Vec4;
+//! use gpu_layout::GpuLayout;
+//!
+//! #[repr(C)]
+//! #[derive(Clone, Copy, GpuLayout)]
+//! struct Light {
+//!     index: u32,
+//!     color: Vec4,
+//! }
+//! ```
+
+use glam::{IVec2, IVec4, Mat4, UVec2, UVec4, Vec2, Vec3, Vec4};
+
+pub use gpu_layout_derive::GpuLayout;
+
+// the derive refers to the crate by its name
+extern crate self as gpu_layout;
+
+/// Offset and size in bytes of a structure field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpuField {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+}
+
+/// A type that can be copied as is into a buffer read by the shaders
+///
+/// # Safety
+///
+/// The size and the offsets of the fields must follow the std430 rules and the type must
+/// not have padding bytes, they would be read uninitialized by `as_bytes`.
+/// Implemented by `#[derive(GpuLayout)]`, which checks them at compile time.
+pub unsafe trait GpuLayout: Copy + 'static {
+    /// Alignment of the type in a std430 buffer
+    const ALIGNMENT: usize;
+    /// Size of the type in a std430 buffer
+    const SIZE: usize = std::mem::size_of::<Self>();
+    /// Fields of a structure in their declaration order, empty for the other types
+    const FIELDS: &'static [GpuField] = &[];
+
+    /// Bytes of the elements as they are copied into a buffer
+    fn as_bytes(elements: &[Self]) -> &[u8] {
+        let size = std::mem::size_of_val(elements);
+        unsafe { std::slice::from_raw_parts(elements.as_ptr() as *const u8, size) }
+    }
+}
+
+/// Rounds the offset up to the next multiple of the alignment
+pub const fn align_to(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}
+
+/// Largest alignment of the fields of a structure
+pub const fn max_alignment(alignments: &[usize]) -> usize {
+    let mut max = 1;
+    let mut i = 0;
+    while i < alignments.len() {
+        if alignments[i] > max {
+            max = alignments[i];
+        }
+        i += 1;
+    }
+    max
+}
+
+macro_rules! impl_gpu_layout {
+    ($($ty:ty => $alignment:expr),* $(,)?) => {
+        $(unsafe impl GpuLayout for $ty {
+            const ALIGNMENT: usize = $alignment;
+        })*
+    };
+}
+
+impl_gpu_layout!(
+    u32 => 4,
+    i32 => 4,
+    f32 => 4,
+    Vec2 => 8,
+    UVec2 => 8,
+    IVec2 => 8,
+    // 12 bytes aligned like a vec4, a scalar can follow it in the last 4 bytes
+    Vec3 => 16,
+    Vec4 => 16,
+    UVec4 => 16,
+    IVec4 => 16,
+    // column major, made of 4 vec4 columns
+    Mat4 => 16,
+);
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3, Vec4};
+
+    use super::{GpuField, GpuLayout};
+
+    #[repr(C)]
+    #[derive(Clone, Copy, GpuLayout)]
+    struct Node {
+        aabb_min: Vec3,
+        left: u32,
+        aabb_max: Vec3,
+        count: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, GpuLayout)]
+    struct Instance {
+        matrix: Mat4,
+        color: Vec4,
+        index: u32,
+        _pad0: u32,
+        _pad1: u32,
+        _pad2: u32,
+    }
+
+    #[test]
+    fn scalars_fill_the_end_of_vec3() {
+        assert_eq!(Node::ALIGNMENT, 16);
+        assert_eq!(Node::SIZE, 32);
+        let offsets: Vec<usize> = Node::FIELDS.iter().map(|field| field.offset).collect();
+        assert_eq!(offsets, [0, 12, 16, 28]);
+    }
+
+    #[test]
+    fn padding_fields_complete_the_structures() {
+        assert_eq!(Instance::SIZE, 96);
+        assert_eq!(Instance::FIELDS.len(), 6);
+        assert_eq!(
+            Instance::FIELDS[2],
+            GpuField {
+                name: "index",
+                offset: 80,
+                size: 4
+            }
+        );
+    }
+}
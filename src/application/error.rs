@@ -0,0 +1,128 @@
+use std::fmt;
+
+use ash::vk;
+
+/// Errors that can stop the application
+#[derive(Debug)]
+pub enum Error {
+    /// The Vulkan library could not be loaded
+    LibraryLoad(String),
+    /// A required instance layer is not available
+    MissingLayer(String),
+    /// A required instance or device extension is not available
+    MissingExtension(String),
+    /// No physical device can run the application
+    NoSuitableDevice,
+    /// No physical device matches the requested name or index
+    DeviceNotFound(String),
+    /// The requested physical device cannot run the application
+    DeviceRejected { name: String, reasons: Vec<String> },
+    /// A compiled shader could not be read
+    ShaderLoad {
+        path: String,
+        source: std::io::Error,
+    },
+    /// A shader could not be compiled by `slangc`
+    ShaderCompile { path: String, message: String },
+    /// The descriptors of a shader could not be read or do not match its resources
+    ShaderReflection(String),
+    /// The host or the device ran out of memory
+    OutOfMemory {
+        context: &'static str,
+        result: vk::Result,
+    },
+    /// The surface of the window is no longer usable
+    SurfaceLost,
+    /// The window or the event loop could not be created
+    Window(String),
+    /// The GUI renderer failed
+    Gui(String),
+    /// A scene or model file could not be loaded
+    SceneLoad { path: String, message: String },
+    /// The scene could not be uploaded to the gpu
+    SceneUpload(String),
+    /// A scene description could not be written
+    SceneSave { path: String, message: String },
+    /// A rendered image could not be written
+    ImageExport {
+        path: String,
+        source: image::ImageError,
+    },
+    /// Any other failed Vulkan call
+    Vulkan {
+        context: &'static str,
+        result: vk::Result,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Maps a failed Vulkan call to the matching variant, the context describes the call
+    /// Used as `.map_err(Error::vulkan("Failed to create the device"))`
+    pub fn vulkan(context: &'static str) -> impl Fn(vk::Result) -> Error {
+        move |result| match result {
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY | vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
+                Error::OutOfMemory { context, result }
+            }
+            vk::Result::ERROR_SURFACE_LOST_KHR => Error::SurfaceLost,
+            _ => Error::Vulkan { context, result },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LibraryLoad(message) => {
+                write!(f, "Failed to load the vulkan library: {}", message)
+            }
+            Error::MissingLayer(layer) => {
+                write!(f, "The required layer {} is not available", layer)
+            }
+            Error::MissingExtension(extension) => {
+                write!(f, "The required extension {} is not available", extension)
+            }
+            Error::NoSuitableDevice => write!(f, "Failed to find a suitable physical device"),
+            Error::DeviceNotFound(device) => write!(f, "No physical device matches {}", device),
+            Error::DeviceRejected { name, reasons } => write!(
+                f,
+                "The physical device {} cannot be used: {}",
+                name,
+                reasons.join(", ")
+            ),
+            Error::ShaderLoad { path, source } => {
+                write!(f, "Failed to load the shader `{}': {}", path, source)
+            }
+            Error::ShaderCompile { path, message } => {
+                write!(f, "Failed to compile the shader `{}': {}", path, message)
+            }
+            Error::ShaderReflection(message) => write!(f, "Shader reflection: {}", message),
+            Error::OutOfMemory { context, result } => write!(f, "{}: {:?}", context, result),
+            Error::SurfaceLost => write!(f, "The window surface was lost"),
+            Error::Window(message) => write!(f, "Failed to create the window: {}", message),
+            Error::Gui(message) => write!(f, "The GUI failed: {}", message),
+            Error::SceneLoad { path, message } => {
+                write!(f, "Failed to load the scene `{}': {}", path, message)
+            }
+            Error::SceneUpload(message) => write!(f, "Failed to upload the scene: {}", message),
+            Error::SceneSave { path, message } => {
+                write!(f, "Failed to save the scene `{}': {}", path, message)
+            }
+            Error::ImageExport { path, source } => {
+                write!(f, "Failed to save the image `{}': {}", path, source)
+            }
+            Error::Vulkan { context, result } => write!(f, "{}: {:?}", context, result),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ShaderLoad { source, .. } => Some(source),
+            Error::ImageExport { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod error;
 pub mod pipelines;
 pub mod scenes;
 pub mod vk_app;
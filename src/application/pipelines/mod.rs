@@ -1,4 +1,10 @@
 pub mod pipeline;
 pub mod pipeline_raytracing;
+pub mod shader_bindings;
+#[cfg(test)]
+mod shader_layout_tests;
+pub mod slangc_flags;
+pub mod spirv_reflection;
 pub mod vk_descriptors;
 pub mod vk_pipeline;
+pub mod vk_shader_reload;
@@ -1,21 +1,120 @@
-use std::path::Path;
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use std::ffi::CString;
 
-use ash::vk::{CommandBuffer, DescriptorSet, DescriptorSetLayout, Pipeline, PipelineLayout};
+use ash::vk::{
+    CommandBuffer, ComputePipelineCreateInfo, DescriptorSet, DescriptorSetLayout,
+    DescriptorSetLayoutCreateFlags, Pipeline, PipelineBindPoint, PipelineCache, PipelineLayout,
+    PipelineLayoutCreateInfo, PipelineShaderStageCreateInfo, PushConstantRange, ShaderStageFlags,
+};
 use ash::{
     util::read_spv,
     vk::{ShaderModule, ShaderModuleCreateInfo},
     Device,
 };
 
-use crate::application::vk_app::VulkanApp;
+use gpu_layout::GpuLayout;
+
+use crate::application::{
+    error::{Error, Result},
+    vk_app::VulkanApp,
+};
 
-use super::vk_descriptors::DescriptorAllocator;
+use super::{
+    slangc_flags::SLANGC_FLAGS,
+    spirv_reflection::{ReflectedBinding, ShaderReflection},
+    vk_descriptors::{DescriptorAllocator, DescriptorLayoutBuilder, DescriptorWriter},
+};
+
+// Generated by `build.rs`: `SHADER_MANIFEST`, every entry point of the top-level shaders
+include!(concat!(env!("OUT_DIR"), "/shader_manifest.rs"));
+
+/// An entry point compiled by `build.rs` and embedded in the binary
+#[derive(Debug, Clone, Copy)]
+pub struct CompiledShader {
+    /// Slang file relative to `src/shaders`
+    pub file: &'static str,
+    pub entry_point: &'static str,
+    pub spv: &'static [u8],
+}
 
 #[derive(Default)]
 pub struct Descriptors {
     pub global_allocator_descriptor: DescriptorAllocator,
-    pub draw_image_descriptors: DescriptorSet,
-    pub draw_image_descriptor_layout: DescriptorSetLayout,
+    /// One set per set number of the shader
+    pub descriptor_sets: Vec<DescriptorSet>,
+    /// The layouts of the sets the shader does not use are empty
+    pub descriptor_set_layouts: Vec<DescriptorSetLayout>,
+}
+
+impl Descriptors {
+    /// Creates the layouts of the descriptor sets declared by a shader and allocates them
+    /// `variable_descriptor_counts` gives the size of the array without size of each set
+    pub fn init(
+        device: &Device,
+        reflection: &ShaderReflection,
+        variable_descriptor_counts: &[u32],
+    ) -> Result<Self> {
+        let mut descriptors = Descriptors::default();
+        if let Err(err) = descriptors.init_sets(device, reflection, variable_descriptor_counts) {
+            descriptors.clear(device);
+            return Err(err);
+        }
+        Ok(descriptors)
+    }
+
+    fn init_sets(
+        &mut self,
+        device: &Device,
+        reflection: &ShaderReflection,
+        variable_descriptor_counts: &[u32],
+    ) -> Result<()> {
+        let set_count = reflection.get_set_count();
+        self.global_allocator_descriptor.init_pool(
+            device,
+            set_count,
+            &reflection.get_pool_size_ratios(variable_descriptor_counts),
+        )?;
+
+        for set in 0..set_count {
+            let mut builder = DescriptorLayoutBuilder::default();
+            builder.add_reflected_bindings(reflection, set)?;
+            let descriptor_set_layout = builder.build(
+                device,
+                reflection.stage,
+                DescriptorSetLayoutCreateFlags::empty(),
+            )?;
+            self.descriptor_set_layouts.push(descriptor_set_layout);
+
+            let descriptor_set = match reflection.find_variable_binding(set) {
+                Some(_) => self.global_allocator_descriptor.allocate_variable(
+                    device,
+                    &descriptor_set_layout,
+                    variable_descriptor_counts
+                        .get(set as usize)
+                        .copied()
+                        .unwrap_or(0),
+                )?,
+                None => self
+                    .global_allocator_descriptor
+                    .allocate(device, &descriptor_set_layout)?,
+            };
+            self.descriptor_sets.push(descriptor_set);
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self, device: &Device) {
+        self.global_allocator_descriptor.destroy_pools(device);
+        for descriptor_set_layout in self.descriptor_set_layouts.drain(..) {
+            unsafe { device.destroy_descriptor_set_layout(descriptor_set_layout, None) };
+        }
+        self.descriptor_sets.clear();
+    }
 }
 
 #[derive(Default)]
@@ -23,51 +122,314 @@ pub struct PipelineAttributes {
     pub descriptors: Descriptors,
     pub pipeline: Pipeline,
     pub pipeline_layout: PipelineLayout,
+    /// Descriptors and push constants of the shader, read when the pipeline is initialized
+    pub reflection: ShaderReflection,
+}
+
+/// Slang file of a pipeline, relative to `src/shaders`, and its entry point
+#[derive(Debug, Clone, Copy)]
+pub struct ShaderSource {
+    pub file: &'static str,
+    pub entry_point: &'static str,
 }
 
 pub struct PipelineUtils;
 
 impl PipelineUtils {
-    pub fn get_compiled_shader_path(shader: &str) -> String {
-        let base_path = Path::new("/target/shaders");
-        let relative_path = Path::new(shader);
-        base_path
-            .join(relative_path)
+    pub fn push_constants<T: GpuLayout>(
+        device: &Device,
+        command_buffer: &CommandBuffer,
+        pipeline_layout: &PipelineLayout,
+        stage_flags: ShaderStageFlags,
+        constants: &T,
+    ) {
+        let data = T::as_bytes(std::slice::from_ref(constants));
+        unsafe {
+            device.cmd_push_constants(*command_buffer, *pipeline_layout, stage_flags, 0, data);
+        }
+    }
+
+    /// Path of the SPIR-V compiled from an entry point, relative to the shader directory
+    /// `build.rs` uses the same layout: `<shader name>/<entry point>.spv`
+    pub fn get_compiled_shader_path(shader_source: &ShaderSource) -> PathBuf {
+        Path::new(shader_source.file)
+            .with_extension("")
+            .join(shader_source.entry_point)
             .with_extension("spv")
-            .to_string_lossy()
-            .into_owned()
     }
 
-    pub fn load_shader_module(file_path: String, device: &Device) -> ShaderModule {
-        let crate_path = env!("CARGO_MANIFEST_DIR");
-        let spv_path = crate_path.to_owned() + &file_path;
-        // open the file. With cursor at the end
-        let mut spv_file = std::fs::File::open(spv_path.clone())
-            .unwrap_or_else(|err| panic!("Failed to open the shader `{}': {:?}", spv_path, err));
+    /// Finds an entry point in the shaders embedded by `build.rs`
+    pub fn find_compiled_shader(shader_source: &ShaderSource) -> Option<&'static CompiledShader> {
+        SHADER_MANIFEST.iter().find(|compiled_shader| {
+            compiled_shader.file == shader_source.file
+                && compiled_shader.entry_point == shader_source.entry_point
+        })
+    }
 
-        let spv_code = read_spv(&mut spv_file)
-            .unwrap_or_else(|err| panic!("Faild to read the shader `{}': {:?}", spv_path, err));
+    /// Compiles a shader of the source directory with `slangc` and the flags of `build.rs`
+    /// The compiled shader of the directory is only replaced when the compilation succeeds
+    pub fn compile_shader(
+        shader_source: &ShaderSource,
+        shader_source_directory: &Path,
+        shader_directory: &Path,
+    ) -> Result<()> {
+        let shader_path = shader_source_directory.join(shader_source.file);
+        let spv_path = shader_directory.join(Self::get_compiled_shader_path(shader_source));
+        let temporary_spv_path = spv_path.with_extension("spv.tmp");
+        let compile_error = |message: String| Error::ShaderCompile {
+            path: shader_path.to_string_lossy().into_owned(),
+            message,
+        };
 
-        let create_info = ShaderModuleCreateInfo::default().code(&spv_code);
+        if let Some(parent) = spv_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| compile_error(err.to_string()))?;
+        }
+        let output = Command::new("slangc")
+            .arg(&shader_path)
+            .args(SLANGC_FLAGS)
+            .arg("-o")
+            .arg(&temporary_spv_path)
+            .arg("-entry")
+            .arg(shader_source.entry_point)
+            .output()
+            .map_err(|err| compile_error(err.to_string()))?;
 
-        unsafe { device.create_shader_module(&create_info, None).unwrap() }
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&temporary_spv_path);
+            // slangc reports the diagnostics with their line numbers on stderr
+            let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+            return Err(compile_error(message));
+        }
+        std::fs::rename(&temporary_spv_path, &spv_path)
+            .map_err(|err| compile_error(err.to_string()))
+    }
+
+    /// Reads the code of a compiled shader, found in the shader directory when it is given
+    /// and contains it, embedded in the binary otherwise
+    pub fn load_shader_code(
+        shader_source: &ShaderSource,
+        shader_directory: Option<&Path>,
+    ) -> Result<Vec<u32>> {
+        let spv_path = shader_directory
+            .map(|shader_directory| {
+                shader_directory.join(Self::get_compiled_shader_path(shader_source))
+            })
+            .filter(|spv_path| spv_path.is_file());
+
+        let spv_code = match spv_path {
+            Some(spv_path) => {
+                let shader_load_error = |source| Error::ShaderLoad {
+                    path: spv_path.to_string_lossy().into_owned(),
+                    source,
+                };
+                let mut spv_file = std::fs::File::open(&spv_path).map_err(shader_load_error)?;
+                read_spv(&mut spv_file).map_err(shader_load_error)?
+            }
+            None => {
+                let shader_load_error = |source| Error::ShaderLoad {
+                    path: format!(
+                        "embedded {} ({})",
+                        shader_source.file, shader_source.entry_point
+                    ),
+                    source,
+                };
+                let spv_bytes = Self::find_compiled_shader(shader_source)
+                    .map(|compiled_shader| compiled_shader.spv)
+                    .ok_or_else(|| shader_load_error(std::io::ErrorKind::NotFound.into()))?;
+                // read_spv copies the bytes, they do not need to be aligned
+                read_spv(&mut Cursor::new(spv_bytes)).map_err(shader_load_error)?
+            }
+        };
+        Ok(spv_code)
+    }
+
+    pub fn load_shader_module(spv_code: &[u32], device: &Device) -> Result<ShaderModule> {
+        let create_info = ShaderModuleCreateInfo::default().code(spv_code);
+
+        unsafe {
+            device
+                .create_shader_module(&create_info, None)
+                .map_err(Error::vulkan("Failed to create the shader module"))
+        }
     }
 }
 
 pub trait ComputePipeline {
     fn get_attributes(&self) -> &PipelineAttributes;
+    fn get_attributes_mut(&mut self) -> &mut PipelineAttributes;
+    /// The shader the compute pipeline is created from
+    fn get_shader_source(&self) -> ShaderSource;
 
-    fn init_descriptors(&mut self, vulkan_app: &mut VulkanApp);
-    fn clear_descriptors(&mut self, device: &Device);
-    fn create_pipeline_layout(&mut self, vulkan_app: &mut VulkanApp);
-    fn create_compute_pipeline(&mut self, vulkan_app: &mut VulkanApp);
+    /// Gives the resources of the descriptors to the writer, by their name in the shader
+    fn write_descriptors(
+        &mut self,
+        vulkan_app: &mut VulkanApp,
+        writer: &mut DescriptorWriter,
+    ) -> Result<()>;
 
     fn run(&mut self, vulkan_app: &mut VulkanApp, command_buffer: &CommandBuffer);
 
-    fn init(&mut self, vulkan_app: &mut VulkanApp) {
-        self.init_descriptors(vulkan_app);
-        self.create_pipeline_layout(vulkan_app);
-        self.create_compute_pipeline(vulkan_app);
+    /// Number of descriptors allocated for a shader array declared without a size
+    fn get_variable_descriptor_count(
+        &self,
+        _vulkan_app: &VulkanApp,
+        _binding: &ReflectedBinding,
+    ) -> u32 {
+        0
+    }
+
+    /// Reads the descriptors declared by the shader, allocates them and writes them
+    fn init_descriptors(&mut self, vulkan_app: &mut VulkanApp, spv_code: &[u32]) -> Result<()> {
+        let reflection = ShaderReflection::from_spirv(spv_code)?;
+        let variable_descriptor_counts: Vec<u32> = (0..reflection.get_set_count())
+            .map(|set| {
+                reflection.find_variable_binding(set).map_or(0, |binding| {
+                    self.get_variable_descriptor_count(vulkan_app, binding)
+                })
+            })
+            .collect();
+        let mut descriptors =
+            Descriptors::init(&vulkan_app.device, &reflection, &variable_descriptor_counts)?;
+
+        let mut writer = DescriptorWriter::new(&reflection);
+        if let Err(err) = self.write_descriptors(vulkan_app, &mut writer) {
+            descriptors.clear(&vulkan_app.device);
+            return Err(err);
+        }
+        writer.update_sets(&vulkan_app.device, &descriptors.descriptor_sets);
+
+        let attributes = self.get_attributes_mut();
+        attributes.descriptors = descriptors;
+        attributes.reflection = reflection;
+        Ok(())
+    }
+
+    fn clear_descriptors(&mut self, device: &Device) {
+        self.get_attributes_mut().descriptors.clear(device);
+    }
+
+    fn create_pipeline_layout(&mut self, vulkan_app: &mut VulkanApp) -> Result<()> {
+        let attributes = self.get_attributes();
+        let push_constant_ranges = self.get_push_constant_ranges();
+        let create_info = PipelineLayoutCreateInfo::default()
+            .set_layouts(&attributes.descriptors.descriptor_set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+
+        let pipeline_layout = unsafe {
+            vulkan_app
+                .device
+                .create_pipeline_layout(&create_info, None)
+                .map_err(Error::vulkan("Failed to create the pipeline layout"))?
+        };
+        self.get_attributes_mut().pipeline_layout = pipeline_layout;
+        Ok(())
+    }
+
+    fn create_compute_pipeline(
+        &mut self,
+        vulkan_app: &mut VulkanApp,
+        spv_code: &[u32],
+    ) -> Result<()> {
+        let attributes = self.get_attributes();
+        // the name in the SPIR-V, slangc renames every compiled entry point `main`
+        let entry_point = CString::new(attributes.reflection.entry_point.as_str())
+            .map_err(|err| Error::ShaderReflection(err.to_string()))?;
+        let shader_module = PipelineUtils::load_shader_module(spv_code, &vulkan_app.device)?;
+        let shader_stage_create_info = PipelineShaderStageCreateInfo::default()
+            .stage(ShaderStageFlags::COMPUTE)
+            .module(shader_module)
+            .name(&entry_point);
+
+        let compute_pipeline_create_info = [ComputePipelineCreateInfo::default()
+            .layout(attributes.pipeline_layout)
+            .stage(shader_stage_create_info)];
+
+        let pipelines = unsafe {
+            vulkan_app.device.create_compute_pipelines(
+                PipelineCache::null(),
+                &compute_pipeline_create_info,
+                None,
+            )
+        };
+
+        unsafe { vulkan_app.device.destroy_shader_module(shader_module, None) };
+
+        let pipelines = pipelines.map_err(|(_, result)| {
+            Error::vulkan("Failed to create the compute pipeline")(result)
+        })?;
+        self.get_attributes_mut().pipeline = pipelines[0];
+        Ok(())
+    }
+
+    /// The push constant ranges to add to the pipeline layout, declared by the shader
+    fn get_push_constant_ranges(&self) -> Vec<PushConstantRange> {
+        self.get_attributes().reflection.get_push_constant_ranges()
+    }
+
+    /// Points the descriptors to the draw images after they were recreated
+    fn update_image_descriptors(&mut self, _vulkan_app: &VulkanApp) -> Result<()> {
+        Ok(())
+    }
+
+    /// Points the descriptors to the model buffers after they were uploaded again, the device must be idle
+    fn update_model_descriptors(&mut self, _vulkan_app: &VulkanApp) -> Result<()> {
+        Ok(())
+    }
+
+    /// Records the push constants before a dispatch
+    fn push_constants(&self, _vulkan_app: &VulkanApp, _command_buffer: &CommandBuffer) {}
+
+    /// Binds the compute pipeline and all its descriptor sets
+    fn bind(&self, device: &Device, command_buffer: &CommandBuffer) {
+        let attributes = self.get_attributes();
+        unsafe {
+            device.cmd_bind_pipeline(
+                *command_buffer,
+                PipelineBindPoint::COMPUTE,
+                attributes.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                *command_buffer,
+                PipelineBindPoint::COMPUTE,
+                attributes.pipeline_layout,
+                0,
+                &attributes.descriptors.descriptor_sets,
+                &[],
+            );
+        }
+    }
+
+    fn init(&mut self, vulkan_app: &mut VulkanApp) -> Result<()> {
+        let spv_code = PipelineUtils::load_shader_code(
+            &self.get_shader_source(),
+            vulkan_app.app_params.shader_directory.as_deref(),
+        )?;
+        self.init_descriptors(vulkan_app, &spv_code)?;
+        self.create_pipeline_layout(vulkan_app)?;
+        self.create_compute_pipeline(vulkan_app, &spv_code)
+    }
+
+    /// Recreates the compute pipeline from the compiled shader, the device must be idle
+    /// The previous pipeline is kept when the creation fails or the descriptors changed
+    fn reload(&mut self, vulkan_app: &mut VulkanApp) -> Result<()> {
+        let spv_code = PipelineUtils::load_shader_code(
+            &self.get_shader_source(),
+            vulkan_app.app_params.shader_directory.as_deref(),
+        )?;
+        let reflection = ShaderReflection::from_spirv(&spv_code)?;
+        let previous_reflection = &self.get_attributes().reflection;
+        if reflection.bindings != previous_reflection.bindings
+            || reflection.push_constants_size != previous_reflection.push_constants_size
+        {
+            return Err(Error::ShaderReflection(String::from(
+                "The descriptors or the push constants of the shader changed, restart to use them",
+            )));
+        }
+
+        let previous_pipeline = self.get_attributes().pipeline;
+        self.create_compute_pipeline(vulkan_app, &spv_code)?;
+        unsafe { vulkan_app.device.destroy_pipeline(previous_pipeline, None) };
+        Ok(())
     }
 
     fn clear(&mut self, device: &Device) {
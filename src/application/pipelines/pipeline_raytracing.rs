@@ -1,233 +1,220 @@
-use std::ffi::CStr;
-
-use ash::{
-    vk::{
-        ComputePipelineCreateInfo, DescriptorBufferInfo, DescriptorImageInfo,
-        DescriptorSetLayoutCreateFlags, DescriptorType, ImageLayout, PipelineBindPoint,
-        PipelineCache, PipelineLayoutCreateInfo, PipelineShaderStageCreateInfo, ShaderStageFlags,
-        WriteDescriptorSet, WHOLE_SIZE,
-    },
-    Device,
-};
+use ash::vk::{ImageLayout, ShaderStageFlags};
+
+use gpu_layout::GpuLayout;
 
-use crate::application::vk_app::VulkanApp;
+use crate::application::{error::Result, scenes::scene::SceneCountsGPU, vk_app::VulkanApp};
 
 use super::{
-    pipeline::{ComputePipeline, Descriptors, PipelineAttributes, PipelineUtils},
-    vk_descriptors::{DescriptorAllocator, DescriptorLayoutBuilder, PoolSizeRatio},
+    pipeline::{ComputePipeline, PipelineAttributes, PipelineUtils, ShaderSource},
+    shader_bindings::raytracing,
+    spirv_reflection::ReflectedBinding,
+    vk_descriptors::DescriptorWriter,
 };
 
+/// Push constants of the raytracing pipeline
+/// The layout matches the `PushConstants` struct in `raytracing.slang`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, GpuLayout)]
+pub struct PushConstantsRaytracing {
+    pub scene_counts: SceneCountsGPU,
+    pub use_bvh: u32,
+    pub max_bounces: u32,
+    pub nb_accumulated_frames: u32,
+    pub samples_per_pixel: u32,
+}
+
 #[derive(Default)]
 pub struct PipelineRaytracing {
     pub base_attributes: PipelineAttributes,
 }
 
-impl ComputePipeline for PipelineRaytracing {
-    fn init_descriptors(&mut self, vulkan_app: &mut VulkanApp) {
-        let pool_size_ratios = [
-            // framebuffer
-            PoolSizeRatio {
-                descriptor_type: DescriptorType::STORAGE_IMAGE,
-                ratio: 1.0,
-            },
-            // triangles buffer
-            PoolSizeRatio {
-                descriptor_type: DescriptorType::STORAGE_BUFFER,
-                ratio: 1.0,
-            },
-            // models buffer
-            PoolSizeRatio {
-                descriptor_type: DescriptorType::STORAGE_BUFFER,
-                ratio: 1.0,
-            },
-            // materials buffer
-            PoolSizeRatio {
-                descriptor_type: DescriptorType::STORAGE_BUFFER,
-                ratio: 1.0,
-            },
-        ];
-
-        let mut global_allocator_descriptor = DescriptorAllocator::default();
-        global_allocator_descriptor.init_pool(&vulkan_app.device, 10, &pool_size_ratios);
-
-        // make the descriptor set layout for our compute draw
-        let mut builder = DescriptorLayoutBuilder::default();
-        // framebuffer
-        builder.add_binding(0, DescriptorType::STORAGE_IMAGE);
-        // triangles buffer
-        builder.add_binding(1, DescriptorType::STORAGE_BUFFER);
-        // models buffer
-        builder.add_binding(2, DescriptorType::STORAGE_BUFFER);
-        // materials buffer
-        builder.add_binding(3, DescriptorType::STORAGE_BUFFER);
-
-        let descriptor_set_layout = builder.build(
-            &vulkan_app.device,
-            ShaderStageFlags::COMPUTE,
-            DescriptorSetLayoutCreateFlags::empty(),
-        );
+impl PipelineRaytracing {
+    /// Writes the descriptors of the images that are recreated when the window is resized
+    fn write_image_descriptors(
+        vulkan_app: &VulkanApp,
+        writer: &mut DescriptorWriter,
+    ) -> Result<()> {
+        writer.write_image(
+            raytracing::Framebuffer::NAME,
+            vulkan_app.draw_image.image_view,
+            ImageLayout::GENERAL,
+        )?;
+        writer.write_image(
+            raytracing::Accumulation::NAME,
+            vulkan_app.accumulation_image.image_view,
+            ImageLayout::GENERAL,
+        )
+    }
+}
 
+impl ComputePipeline for PipelineRaytracing {
+    fn write_descriptors(
+        &mut self,
+        vulkan_app: &mut VulkanApp,
+        writer: &mut DescriptorWriter,
+    ) -> Result<()> {
+        vulkan_app.scene.build_acceleration_structures();
         let scene_buffers_gpu = {
             let scene = &vulkan_app.scene;
-            scene.upload_buffers(vulkan_app)
+            scene.upload_buffers(vulkan_app)?
         };
 
-        // allocate a descriptor set for our draw image and buffer
-        let descriptor_set =
-            global_allocator_descriptor.allocate(&vulkan_app.device, &descriptor_set_layout);
-
-        // frame buffer
-        let descriptor_framebuffer_info = [DescriptorImageInfo::default()
-            .image_view(vulkan_app.draw_image.image_view)
-            .image_layout(ImageLayout::GENERAL)];
-        // triangles buffer
-        let descriptor_triangles_buffer_info = [DescriptorBufferInfo::default()
-            .buffer(scene_buffers_gpu.triangles_buffer.buffer.buffer)
-            .range(WHOLE_SIZE)
-            .offset(0)];
-        // models buffer
-        let descriptor_models_buffer_info = [DescriptorBufferInfo::default()
-            .buffer(scene_buffers_gpu.models_buffer.buffer.buffer)
-            .range(WHOLE_SIZE)
-            .offset(0)];
-        // materials buffer
-        let descriptor_materials_buffer_info = [DescriptorBufferInfo::default()
-            .buffer(scene_buffers_gpu.materials_buffer.buffer.buffer)
-            .range(WHOLE_SIZE)
-            .offset(0)];
-
-        let descriptor_writes = [
-            // framebuffer binding in set 0
-            WriteDescriptorSet::default()
-                .dst_set(descriptor_set)
-                .dst_binding(0) // binding within the set
-                .descriptor_count(1)
-                .descriptor_type(DescriptorType::STORAGE_IMAGE)
-                .image_info(&descriptor_framebuffer_info),
-            // triangles buffer in set 0
-            WriteDescriptorSet::default()
-                .dst_set(descriptor_set)
-                .dst_binding(1) // binding within the set
-                .descriptor_count(1)
-                .descriptor_type(DescriptorType::STORAGE_BUFFER)
-                .buffer_info(&descriptor_triangles_buffer_info),
-            // models buffer in set 0
-            WriteDescriptorSet::default()
-                .dst_set(descriptor_set)
-                .dst_binding(2) // binding within the set
-                .descriptor_count(1)
-                .descriptor_type(DescriptorType::STORAGE_BUFFER)
-                .buffer_info(&descriptor_models_buffer_info),
-            // materials buffer in set 0
-            WriteDescriptorSet::default()
-                .dst_set(descriptor_set)
-                .dst_binding(3) // binding within the set
-                .descriptor_count(1)
-                .descriptor_type(DescriptorType::STORAGE_BUFFER)
-                .buffer_info(&descriptor_materials_buffer_info),
-        ];
-
+        writer.write_buffer(
+            raytracing::Triangles::NAME,
+            scene_buffers_gpu.triangles_buffer.buffer.buffer,
+        )?;
+        writer.write_buffer(
+            raytracing::Models::NAME,
+            scene_buffers_gpu.models_buffer.buffer.buffer,
+        )?;
+        writer.write_buffer(
+            raytracing::Materials::NAME,
+            scene_buffers_gpu.materials_buffer.buffer.buffer,
+        )?;
+        writer.write_buffer(
+            raytracing::Camera::NAME,
+            scene_buffers_gpu.camera_buffer.buffer.buffer,
+        )?;
+        writer.write_buffer(
+            raytracing::TlasNodes::NAME,
+            scene_buffers_gpu.tlas_nodes_buffer.buffer.buffer,
+        )?;
+        writer.write_buffer(
+            raytracing::TlasModelIndices::NAME,
+            scene_buffers_gpu.tlas_model_indices_buffer.buffer.buffer,
+        )?;
+        writer.write_buffer(
+            raytracing::BlasNodes::NAME,
+            scene_buffers_gpu.blas_nodes_buffer.buffer.buffer,
+        )?;
+        writer.write_buffer(
+            raytracing::BlasTriangleIndices::NAME,
+            scene_buffers_gpu.blas_triangle_indices_buffer.buffer.buffer,
+        )?;
+        writer.write_buffer(
+            raytracing::Meshes::NAME,
+            scene_buffers_gpu.meshes_buffer.buffer.buffer,
+        )?;
+
+        // the materials index the textures of the scene
+        let texture_views: Vec<_> = scene_buffers_gpu
+            .textures
+            .iter()
+            .map(|texture| texture.image_view)
+            .collect();
+        writer.write_images(
+            raytracing::Textures::NAME,
+            &texture_views,
+            ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )?;
+        writer.write_sampler(raytracing::Sampler::NAME, scene_buffers_gpu.texture_sampler)?;
         vulkan_app.scene.buffers = Some(scene_buffers_gpu);
 
-        unsafe {
-            vulkan_app
-                .device
-                .update_descriptor_sets(&descriptor_writes, &[]);
-        }
+        Self::write_image_descriptors(vulkan_app, writer)
+    }
 
-        self.base_attributes.descriptors = Descriptors {
-            global_allocator_descriptor,
-            draw_image_descriptors: descriptor_set,
-            draw_image_descriptor_layout: descriptor_set_layout,
+    fn get_variable_descriptor_count(
+        &self,
+        vulkan_app: &VulkanApp,
+        binding: &ReflectedBinding,
+    ) -> u32 {
+        match binding.name.as_str() {
+            raytracing::Textures::NAME => vulkan_app.scene.textures.len() as u32,
+            _ => 0,
         }
     }
 
-    fn clear_descriptors(&mut self, device: &Device) {
-        unsafe {
-            self.base_attributes
-                .descriptors
-                .global_allocator_descriptor
-                .clear_pool(device);
-            device.destroy_descriptor_set_layout(
-                self.base_attributes
-                    .descriptors
-                    .draw_image_descriptor_layout,
-                None,
-            );
-        }
+    fn get_attributes(&self) -> &PipelineAttributes {
+        &self.base_attributes
     }
 
-    fn create_pipeline_layout(&mut self, vulkan_app: &mut VulkanApp) {
-        let layouts = [self
-            .base_attributes
-            .descriptors
-            .draw_image_descriptor_layout];
-        let create_info = PipelineLayoutCreateInfo::default().set_layouts(&layouts);
+    fn get_attributes_mut(&mut self) -> &mut PipelineAttributes {
+        &mut self.base_attributes
+    }
 
-        unsafe {
-            self.base_attributes.pipeline_layout = vulkan_app
-                .device
-                .create_pipeline_layout(&create_info, None)
-                .unwrap();
+    fn get_shader_source(&self) -> ShaderSource {
+        ShaderSource {
+            file: "raytracing.slang",
+            entry_point: "main",
         }
     }
 
-    fn create_compute_pipeline(&mut self, vulkan_app: &mut VulkanApp) {
-        let shader_module = PipelineUtils::load_shader_module(
-            PipelineUtils::get_compiled_shader_path("raytracing.slang"),
+    fn update_image_descriptors(&mut self, vulkan_app: &VulkanApp) -> Result<()> {
+        let mut writer = DescriptorWriter::new(&self.base_attributes.reflection);
+        Self::write_image_descriptors(vulkan_app, &mut writer)?;
+        writer.update_sets(
             &vulkan_app.device,
+            &self.base_attributes.descriptors.descriptor_sets,
         );
-        let shader_stage_create_info = PipelineShaderStageCreateInfo::default()
-            .stage(ShaderStageFlags::COMPUTE)
-            .module(shader_module)
-            .name(CStr::from_bytes_with_nul(b"main\0").unwrap());
-
-        let compute_pipeline_create_info = [ComputePipelineCreateInfo::default()
-            .layout(self.base_attributes.pipeline_layout)
-            .stage(shader_stage_create_info)];
-
-        unsafe {
-            self.base_attributes.pipeline = vulkan_app
-                .device
-                .create_compute_pipelines(
-                    PipelineCache::null(),
-                    &compute_pipeline_create_info,
-                    None,
-                )
-                .unwrap()[0];
-        }
+        Ok(())
+    }
 
-        unsafe { vulkan_app.device.destroy_shader_module(shader_module, None) };
+    fn update_model_descriptors(&mut self, vulkan_app: &VulkanApp) -> Result<()> {
+        let scene_buffers_gpu = match vulkan_app.scene.buffers {
+            Some(ref buffers) => buffers,
+            None => return Ok(()),
+        };
+        let mut writer = DescriptorWriter::new(&self.base_attributes.reflection);
+        writer.write_buffer(
+            raytracing::Models::NAME,
+            scene_buffers_gpu.models_buffer.buffer.buffer,
+        )?;
+        writer.write_buffer(
+            raytracing::TlasNodes::NAME,
+            scene_buffers_gpu.tlas_nodes_buffer.buffer.buffer,
+        )?;
+        writer.write_buffer(
+            raytracing::TlasModelIndices::NAME,
+            scene_buffers_gpu.tlas_model_indices_buffer.buffer.buffer,
+        )?;
+        writer.update_sets(
+            &vulkan_app.device,
+            &self.base_attributes.descriptors.descriptor_sets,
+        );
+        Ok(())
     }
 
-    fn get_attributes(&self) -> &PipelineAttributes {
-        &self.base_attributes
+    fn push_constants(&self, vulkan_app: &VulkanApp, command_buffer: &ash::vk::CommandBuffer) {
+        // the counts of the elements currently uploaded to the gpu
+        let scene_counts = match vulkan_app.scene.buffers {
+            Some(ref buffers) => buffers.counts,
+            None => SceneCountsGPU::default(),
+        };
+        let push_constants = PushConstantsRaytracing {
+            scene_counts,
+            use_bvh: vulkan_app.render_params.use_bvh as u32,
+            max_bounces: vulkan_app.render_params.max_bounces,
+            nb_accumulated_frames: vulkan_app.nb_accumulated_frames,
+            samples_per_pixel: vulkan_app.render_params.samples_per_pixel,
+        };
+        PipelineUtils::push_constants(
+            &vulkan_app.device,
+            command_buffer,
+            &self.base_attributes.pipeline_layout,
+            ShaderStageFlags::COMPUTE,
+            &push_constants,
+        );
     }
 
     fn run(&mut self, vulkan_app: &mut VulkanApp, command_buffer: &ash::vk::CommandBuffer) {
-        unsafe {
-            // bind the gradient drawing compute pipeline
-            vulkan_app.device.cmd_bind_pipeline(
-                *command_buffer,
-                PipelineBindPoint::COMPUTE,
-                self.base_attributes.pipeline,
-            );
+        // upload the camera of the current frame
+        vulkan_app
+            .scene
+            .update_camera_buffer(&vulkan_app.device, command_buffer);
 
-            // bind the descriptor set containing the draw image for the compute pipeline
-            vulkan_app.device.cmd_bind_descriptor_sets(
-                *command_buffer,
-                PipelineBindPoint::COMPUTE,
-                self.base_attributes.pipeline_layout,
-                0,
-                &[self.base_attributes.descriptors.draw_image_descriptors],
-                &[],
-            );
+        // bind the raytracing pipeline and the descriptor sets of the scene and the images
+        self.bind(&vulkan_app.device, command_buffer);
 
+        // push the scene element counts, the rendering parameters and the accumulation state
+        self.push_constants(vulkan_app, command_buffer);
+
+        unsafe {
             // execute the compute pipeline dispatch. We are using 16x16 workgroup size so we need to divide by it
+            // the window can have any size so the last workgroups are partially outside of the image
             vulkan_app.device.cmd_dispatch(
                 *command_buffer,
-                vulkan_app.draw_extent.width / 16,
-                vulkan_app.draw_extent.height / 16,
+                vulkan_app.draw_extent.width.div_ceil(16),
+                vulkan_app.draw_extent.height.div_ceil(16),
                 1,
             );
         }
@@ -0,0 +1,18 @@
+//! Descriptors declared by the shaders, one module per shader of `src/shaders`
+//! Generated by `build.rs` from the reflection of `slangc`
+
+use ash::vk::DescriptorType;
+
+/// A shader parameter bound to a descriptor
+#[derive(Debug, Clone, Copy)]
+pub struct ShaderBinding {
+    /// Name of the parameter in the shader
+    pub name: &'static str,
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    /// 0 for the arrays declared without a size
+    pub descriptor_count: u32,
+}
+
+include!(concat!(env!("OUT_DIR"), "/shader_bindings.rs"));
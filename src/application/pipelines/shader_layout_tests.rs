@@ -0,0 +1,153 @@
+//! Compares the layout of the structures uploaded to the shaders with the reflection
+//! written by `slangc` next to the compiled shaders, and the descriptors generated from it
+//! with the SPIR-V
+
+use gpu_layout::GpuLayout;
+use serde_json::Value;
+
+use crate::application::scenes::{
+    bvh::BvhNodeGPU, camera::CameraGPU, material::Material, mesh::MeshGPU, model::ModelGPU,
+    scene::SceneCountsGPU, triangle::Triangle,
+};
+
+use super::{
+    pipeline::{PipelineUtils, ShaderSource},
+    pipeline_raytracing::PushConstantsRaytracing,
+    shader_bindings::raytracing,
+    spirv_reflection::ShaderReflection,
+};
+
+fn load_reflection(shader: &str, entry_point: &str) -> Value {
+    let path = format!(
+        "{}/target/shaders/{}/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        shader,
+        entry_point
+    );
+    let content = std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Failed to read the reflection {}: {}", path, err));
+    serde_json::from_str(&content).unwrap()
+}
+
+/// Offset and size of a field in a buffer, the plain types have no binding
+fn get_uniform_binding(field: &Value) -> Option<(usize, usize)> {
+    let bindings = match (&field["binding"], &field["bindings"]) {
+        (binding @ Value::Object(_), _) => vec![binding],
+        (_, Value::Array(bindings)) => bindings.iter().collect(),
+        _ => Vec::new(),
+    };
+    bindings
+        .into_iter()
+        .find(|binding| binding["kind"] == "uniform")
+        .map(|binding| {
+            (
+                binding["offset"].as_u64().unwrap() as usize,
+                binding["size"].as_u64().unwrap() as usize,
+            )
+        })
+}
+
+/// Finds the offsets and sizes of the fields of a struct type used by a shader parameter
+fn find_struct_layout(value: &Value, name: &str) -> Option<Vec<(String, usize, usize)>> {
+    match value {
+        Value::Object(object) => {
+            if object.get("kind").is_some_and(|kind| kind == "struct")
+                && object
+                    .get("name")
+                    .is_some_and(|struct_name| struct_name == name)
+            {
+                let layout: Option<Vec<_>> = object["fields"]
+                    .as_array()?
+                    .iter()
+                    .map(|field| {
+                        let (offset, size) = get_uniform_binding(field)?;
+                        Some((field["name"].as_str()?.to_owned(), offset, size))
+                    })
+                    .collect();
+                if layout.is_some() {
+                    return layout;
+                }
+            }
+            object
+                .values()
+                .find_map(|value| find_struct_layout(value, name))
+        }
+        Value::Array(values) => values
+            .iter()
+            .find_map(|value| find_struct_layout(value, name)),
+        _ => None,
+    }
+}
+
+/// The fields are matched in their declaration order, their names may differ
+fn assert_layout_matches<T: GpuLayout>(reflection: &Value, slang_name: &str) {
+    let slang_fields = find_struct_layout(reflection, slang_name)
+        .unwrap_or_else(|| panic!("The struct {} is not in the reflection", slang_name));
+    let rust_name = std::any::type_name::<T>();
+
+    assert_eq!(
+        T::FIELDS.len(),
+        slang_fields.len(),
+        "{} and {} do not have the same number of fields",
+        rust_name,
+        slang_name
+    );
+    for (rust_field, (slang_field, offset, size)) in T::FIELDS.iter().zip(&slang_fields) {
+        assert_eq!(
+            (rust_field.offset, rust_field.size),
+            (*offset, *size),
+            "{}::{} does not match {}::{} (offset, size)",
+            rust_name,
+            rust_field.name,
+            slang_name,
+            slang_field
+        );
+    }
+}
+
+#[test]
+fn raytracing_structures_match_the_shader() {
+    let reflection = load_reflection("raytracing", "main");
+
+    assert_layout_matches::<Triangle>(&reflection, "Triangle");
+    assert_layout_matches::<ModelGPU>(&reflection, "Model");
+    assert_layout_matches::<Material>(&reflection, "Material");
+    assert_layout_matches::<CameraGPU>(&reflection, "Camera");
+    assert_layout_matches::<MeshGPU>(&reflection, "Mesh");
+    assert_layout_matches::<BvhNodeGPU>(&reflection, "BvhNode");
+    assert_layout_matches::<SceneCountsGPU>(&reflection, "SceneCounts");
+    assert_layout_matches::<PushConstantsRaytracing>(&reflection, "PushConstants");
+}
+
+#[test]
+fn raytracing_bindings_match_the_spirv() {
+    let shader_source = ShaderSource {
+        file: "raytracing.slang",
+        entry_point: "main",
+    };
+    let spv_code = PipelineUtils::load_shader_code(&shader_source, None).unwrap();
+    let reflection = ShaderReflection::from_spirv(&spv_code).unwrap();
+
+    assert_eq!(reflection.bindings.len(), raytracing::BINDINGS.len());
+    for shader_binding in raytracing::BINDINGS {
+        let binding = reflection
+            .find_binding(shader_binding.name)
+            .unwrap_or_else(|| panic!("{} is not in the SPIR-V", shader_binding.name));
+        assert_eq!(
+            (
+                binding.set,
+                binding.binding,
+                binding.descriptor_type,
+                binding.descriptor_count
+            ),
+            (
+                shader_binding.set,
+                shader_binding.binding,
+                shader_binding.descriptor_type,
+                shader_binding.descriptor_count
+            ),
+            "{} does not match the SPIR-V",
+            shader_binding.name
+        );
+    }
+}
@@ -0,0 +1,12 @@
+//! Flags of `slangc` shared by `build.rs` and the hot reload of the shaders
+
+/// Compiles to SPIR-V, the source and output paths and the entry point are added by the caller
+/// `-g2` keeps the names of the parameters, the descriptors are found by their name
+pub const SLANGC_FLAGS: &[&str] = &[
+    "-emit-spirv-directly",
+    "-g2",
+    "-profile",
+    "glsl_460",
+    "-target",
+    "spirv",
+];
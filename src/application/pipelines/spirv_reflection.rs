@@ -0,0 +1,565 @@
+//! Reads the descriptors and the push constants declared by a SPIR-V module
+//! Only the instructions needed to create the layouts of a pipeline are decoded
+
+use std::collections::{BTreeMap, HashMap};
+
+use ash::vk::{DescriptorType, PushConstantRange, ShaderStageFlags};
+
+use crate::application::error::{Error, Result};
+
+use super::vk_descriptors::PoolSizeRatio;
+
+const SPIRV_MAGIC_NUMBER: u32 = 0x0723_0203;
+const SPIRV_HEADER_SIZE: usize = 5;
+
+// opcodes
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const OP_TYPE_ACCELERATION_STRUCTURE: u32 = 5341;
+
+// decorations
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// storage classes
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+// image properties
+const DIM_BUFFER: u32 = 5;
+const SAMPLED_STORAGE: u32 = 2;
+
+/// The types needed to find the descriptor types and the push constants size
+enum SpirvType {
+    Scalar { size: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { storage_class: u32, pointee: u32 },
+    AccelerationStructure,
+}
+
+/// The instructions of a module used by the reflection
+#[derive(Default)]
+struct SpirvModule {
+    entry_point: Option<(u32, String)>,
+    names: HashMap<u32, String>,
+    types: HashMap<u32, SpirvType>,
+    constants: HashMap<u32, u32>,
+    /// Value of the decorations, 0 for the decorations without operand
+    decorations: HashMap<(u32, u32), u32>,
+    member_decorations: HashMap<(u32, u32, u32), u32>,
+    /// Result id and pointer type of the global variables
+    variables: Vec<(u32, u32)>,
+}
+
+/// A shader parameter bound to a descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflectedBinding {
+    /// Name of the parameter in the shader, the compiler must keep the debug names
+    pub name: String,
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: DescriptorType,
+    /// 0 for the arrays declared without a size
+    pub descriptor_count: u32,
+}
+
+/// Descriptors and push constants declared by the entry point of a SPIR-V module
+#[derive(Debug, Default, Clone)]
+pub struct ShaderReflection {
+    pub entry_point: String,
+    pub stage: ShaderStageFlags,
+    /// Sorted by set then binding
+    pub bindings: Vec<ReflectedBinding>,
+    /// Size of the push constants block, 0 without it
+    pub push_constants_size: u32,
+}
+
+fn reflection_error(message: impl Into<String>) -> Error {
+    Error::ShaderReflection(message.into())
+}
+
+/// Reads a nul terminated string packed in words
+fn read_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn get_stage(execution_model: u32) -> ShaderStageFlags {
+    match execution_model {
+        0 => ShaderStageFlags::VERTEX,
+        4 => ShaderStageFlags::FRAGMENT,
+        5 => ShaderStageFlags::COMPUTE,
+        5313 => ShaderStageFlags::RAYGEN_KHR,
+        5314 => ShaderStageFlags::INTERSECTION_KHR,
+        5315 => ShaderStageFlags::ANY_HIT_KHR,
+        5316 => ShaderStageFlags::CLOSEST_HIT_KHR,
+        5317 => ShaderStageFlags::MISS_KHR,
+        5318 => ShaderStageFlags::CALLABLE_KHR,
+        _ => ShaderStageFlags::ALL,
+    }
+}
+
+impl SpirvModule {
+    fn parse(code: &[u32]) -> Result<Self> {
+        if code.len() < SPIRV_HEADER_SIZE || code[0] != SPIRV_MAGIC_NUMBER {
+            return Err(reflection_error("The code is not a SPIR-V module"));
+        }
+
+        let mut module = SpirvModule::default();
+        let mut position = SPIRV_HEADER_SIZE;
+        while position < code.len() {
+            let word_count = (code[position] >> 16) as usize;
+            let opcode = code[position] & 0xffff;
+            if word_count == 0 || position + word_count > code.len() {
+                return Err(reflection_error(format!(
+                    "Invalid instruction at word {}",
+                    position
+                )));
+            }
+            let operands = &code[position + 1..position + word_count];
+            module.add_instruction(opcode, operands);
+            position += word_count;
+        }
+        Ok(module)
+    }
+
+    /// Records the instructions used by the reflection, the others are skipped
+    fn add_instruction(&mut self, opcode: u32, operands: &[u32]) {
+        // the operands of the instructions that are too short are ignored
+        let operand = |index: usize| operands.get(index).copied().unwrap_or(0);
+        match opcode {
+            OP_NAME => {
+                self.names.insert(operand(0), read_string(&operands[1..]));
+            }
+            OP_ENTRY_POINT if self.entry_point.is_none() && operands.len() > 2 => {
+                self.entry_point = Some((operand(0), read_string(&operands[2..])));
+            }
+            OP_TYPE_INT | OP_TYPE_FLOAT => {
+                let size = operand(1) / 8;
+                self.types.insert(operand(0), SpirvType::Scalar { size });
+            }
+            OP_TYPE_VECTOR => {
+                let (component, count) = (operand(1), operand(2));
+                self.types
+                    .insert(operand(0), SpirvType::Vector { component, count });
+            }
+            OP_TYPE_MATRIX => {
+                let (column, count) = (operand(1), operand(2));
+                self.types
+                    .insert(operand(0), SpirvType::Matrix { column, count });
+            }
+            OP_TYPE_IMAGE => {
+                let (dim, sampled) = (operand(2), operand(6));
+                self.types
+                    .insert(operand(0), SpirvType::Image { dim, sampled });
+            }
+            OP_TYPE_SAMPLER => {
+                self.types.insert(operand(0), SpirvType::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0), SpirvType::SampledImage);
+            }
+            OP_TYPE_ACCELERATION_STRUCTURE => {
+                self.types
+                    .insert(operand(0), SpirvType::AccelerationStructure);
+            }
+            OP_TYPE_ARRAY => {
+                let (element, length) = (operand(1), operand(2));
+                self.types
+                    .insert(operand(0), SpirvType::Array { element, length });
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                let element = operand(1);
+                self.types
+                    .insert(operand(0), SpirvType::RuntimeArray { element });
+            }
+            OP_TYPE_STRUCT => {
+                let members = operands[1..].to_vec();
+                self.types.insert(operand(0), SpirvType::Struct { members });
+            }
+            OP_TYPE_POINTER => {
+                let (storage_class, pointee) = (operand(1), operand(2));
+                self.types.insert(
+                    operand(0),
+                    SpirvType::Pointer {
+                        storage_class,
+                        pointee,
+                    },
+                );
+            }
+            // only the 32 bit constants are used, as array lengths
+            OP_CONSTANT => {
+                self.constants.insert(operand(1), operand(2));
+            }
+            // the variables declared in the functions come after them and are not global
+            OP_VARIABLE => {
+                self.variables.push((operand(1), operand(0)));
+            }
+            OP_DECORATE => {
+                self.decorations
+                    .insert((operand(0), operand(1)), operand(2));
+            }
+            OP_MEMBER_DECORATE => {
+                self.member_decorations
+                    .insert((operand(0), operand(1), operand(2)), operand(3));
+            }
+            _ => {}
+        }
+    }
+
+    fn get_type(&self, type_id: u32) -> Result<&SpirvType> {
+        self.types
+            .get(&type_id)
+            .ok_or_else(|| reflection_error(format!("Unknown type %{}", type_id)))
+    }
+
+    fn get_name(&self, id: u32) -> String {
+        self.names.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// Size in bytes of a type in a block, using the strides and offsets of its decorations
+    fn get_type_size(&self, type_id: u32) -> Result<u32> {
+        let size = match self.get_type(type_id)? {
+            SpirvType::Scalar { size } => *size,
+            SpirvType::Vector { component, count } => self.get_type_size(*component)? * count,
+            SpirvType::Matrix { column, count } => self.get_type_size(*column)? * count,
+            SpirvType::Array { element, length } => {
+                let length = self.constants.get(length).copied().unwrap_or(0);
+                let stride = match self.decorations.get(&(type_id, DECORATION_ARRAY_STRIDE)) {
+                    Some(&stride) => stride,
+                    None => self.get_type_size(*element)?,
+                };
+                stride * length
+            }
+            SpirvType::RuntimeArray { .. } => 0,
+            SpirvType::Struct { members } => {
+                let mut size = 0;
+                for (index, &member) in members.iter().enumerate() {
+                    let index = index as u32;
+                    let offset = self
+                        .member_decorations
+                        .get(&(type_id, index, DECORATION_OFFSET))
+                        .copied()
+                        .unwrap_or(size);
+                    // the matrices of the blocks are laid out with their stride
+                    let member_size = match (
+                        self.get_type(member)?,
+                        self.member_decorations
+                            .get(&(type_id, index, DECORATION_MATRIX_STRIDE)),
+                    ) {
+                        (SpirvType::Matrix { count, .. }, Some(&stride)) => stride * count,
+                        _ => self.get_type_size(member)?,
+                    };
+                    size = size.max(offset + member_size);
+                }
+                size
+            }
+            _ => 0,
+        };
+        Ok(size)
+    }
+
+    /// Descriptor type and count of a global variable, `None` when it is not a descriptor
+    fn get_descriptor_type(
+        &self,
+        storage_class: u32,
+        pointee: u32,
+    ) -> Result<Option<(DescriptorType, u32)>> {
+        // the arrays of resources are arrays of descriptors
+        let (resource_type, descriptor_count) = match self.get_type(pointee)? {
+            SpirvType::Array { element, length } => {
+                (*element, self.constants.get(length).copied().unwrap_or(0))
+            }
+            SpirvType::RuntimeArray { element } => (*element, 0),
+            _ => (pointee, 1),
+        };
+
+        let descriptor_type = match (storage_class, self.get_type(resource_type)?) {
+            (STORAGE_CLASS_UNIFORM_CONSTANT, SpirvType::Image { dim, sampled }) => {
+                match (*dim, *sampled) {
+                    (DIM_BUFFER, SAMPLED_STORAGE) => DescriptorType::STORAGE_TEXEL_BUFFER,
+                    (DIM_BUFFER, _) => DescriptorType::UNIFORM_TEXEL_BUFFER,
+                    (_, SAMPLED_STORAGE) => DescriptorType::STORAGE_IMAGE,
+                    _ => DescriptorType::SAMPLED_IMAGE,
+                }
+            }
+            (STORAGE_CLASS_UNIFORM_CONSTANT, SpirvType::SampledImage) => {
+                DescriptorType::COMBINED_IMAGE_SAMPLER
+            }
+            (STORAGE_CLASS_UNIFORM_CONSTANT, SpirvType::Sampler) => DescriptorType::SAMPLER,
+            (STORAGE_CLASS_UNIFORM_CONSTANT, SpirvType::AccelerationStructure) => {
+                DescriptorType::ACCELERATION_STRUCTURE_KHR
+            }
+            // before SPIR-V 1.3, the storage buffers are uniform buffer blocks
+            (STORAGE_CLASS_UNIFORM, _)
+                if self
+                    .decorations
+                    .contains_key(&(resource_type, DECORATION_BUFFER_BLOCK)) =>
+            {
+                DescriptorType::STORAGE_BUFFER
+            }
+            (STORAGE_CLASS_UNIFORM, _) => DescriptorType::UNIFORM_BUFFER,
+            (STORAGE_CLASS_STORAGE_BUFFER, _) => DescriptorType::STORAGE_BUFFER,
+            _ => return Ok(None),
+        };
+        Ok(Some((descriptor_type, descriptor_count)))
+    }
+}
+
+impl ShaderReflection {
+    /// Reads the descriptors and the push constants of the first entry point of the module
+    /// Every global variable is listed, used by the entry point or not
+    pub fn from_spirv(code: &[u32]) -> Result<Self> {
+        let module = SpirvModule::parse(code)?;
+        let (execution_model, entry_point) = module
+            .entry_point
+            .clone()
+            .ok_or_else(|| reflection_error("The module has no entry point"))?;
+
+        let mut bindings = Vec::new();
+        let mut push_constants_size = 0;
+        for &(variable, pointer_type) in &module.variables {
+            let SpirvType::Pointer {
+                storage_class,
+                pointee,
+            } = *module.get_type(pointer_type)?
+            else {
+                continue;
+            };
+
+            if storage_class == STORAGE_CLASS_PUSH_CONSTANT {
+                push_constants_size = module.get_type_size(pointee)?;
+                continue;
+            }
+            let Some(&binding) = module.decorations.get(&(variable, DECORATION_BINDING)) else {
+                continue;
+            };
+            let Some((descriptor_type, descriptor_count)) =
+                module.get_descriptor_type(storage_class, pointee)?
+            else {
+                continue;
+            };
+
+            // the blocks without an instance name are named by their type
+            let mut name = module.get_name(variable);
+            if name.is_empty() {
+                name = module.get_name(pointee);
+            }
+            bindings.push(ReflectedBinding {
+                name,
+                set: module
+                    .decorations
+                    .get(&(variable, DECORATION_DESCRIPTOR_SET))
+                    .copied()
+                    .unwrap_or(0),
+                binding,
+                descriptor_type,
+                descriptor_count,
+            });
+        }
+        bindings.sort_by_key(|binding| (binding.set, binding.binding));
+
+        Ok(Self {
+            entry_point,
+            stage: get_stage(execution_model),
+            bindings,
+            push_constants_size,
+        })
+    }
+
+    pub fn find_binding(&self, name: &str) -> Option<&ReflectedBinding> {
+        self.bindings.iter().find(|binding| binding.name == name)
+    }
+
+    /// Number of descriptor set layouts of the pipeline layout, the unused sets are empty
+    pub fn get_set_count(&self) -> u32 {
+        self.bindings
+            .iter()
+            .map(|binding| binding.set + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The array declared without a size of a set, it must be its last binding
+    pub fn find_variable_binding(&self, set: u32) -> Option<&ReflectedBinding> {
+        self.bindings
+            .iter()
+            .rfind(|binding| binding.set == set)
+            .filter(|binding| binding.descriptor_count == 0)
+    }
+
+    pub fn get_push_constant_ranges(&self) -> Vec<PushConstantRange> {
+        if self.push_constants_size == 0 {
+            return Vec::new();
+        }
+        vec![PushConstantRange::default()
+            .stage_flags(self.stage)
+            .offset(0)
+            .size(self.push_constants_size)]
+    }
+
+    /// Pool ratios allocating every descriptor set of the shader once
+    /// The arrays without size of each set count the descriptors of `variable_descriptor_counts`
+    pub fn get_pool_size_ratios(&self, variable_descriptor_counts: &[u32]) -> Vec<PoolSizeRatio> {
+        let mut descriptor_counts: BTreeMap<i32, u32> = BTreeMap::new();
+        for binding in &self.bindings {
+            let descriptor_count = match binding.descriptor_count {
+                0 => variable_descriptor_counts
+                    .get(binding.set as usize)
+                    .copied()
+                    .unwrap_or(0),
+                descriptor_count => descriptor_count,
+            };
+            *descriptor_counts
+                .entry(binding.descriptor_type.as_raw())
+                .or_default() += descriptor_count;
+        }
+        let set_count = self.get_set_count().max(1) as f32;
+        descriptor_counts
+            .into_iter()
+            .map(|(descriptor_type, count)| PoolSizeRatio {
+                descriptor_type: DescriptorType::from_raw(descriptor_type),
+                ratio: count as f32 / set_count,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use ash::{
+        util::read_spv,
+        vk::{DescriptorType, ShaderStageFlags},
+    };
+
+    use super::{ReflectedBinding, ShaderReflection};
+
+    /// Compiled from `test_shaders/reflection.slang`, checked in so the tests run without `slangc`
+    fn load_test_shader() -> Vec<u32> {
+        let bytes = include_bytes!("test_shaders/reflection.spv");
+        read_spv(&mut Cursor::new(&bytes[..])).unwrap()
+    }
+
+    fn binding(
+        name: &str,
+        set: u32,
+        binding: u32,
+        descriptor_type: DescriptorType,
+        descriptor_count: u32,
+    ) -> ReflectedBinding {
+        ReflectedBinding {
+            name: name.to_owned(),
+            set,
+            binding,
+            descriptor_type,
+            descriptor_count,
+        }
+    }
+
+    #[test]
+    fn reads_the_entry_point() {
+        let reflection = ShaderReflection::from_spirv(&load_test_shader()).unwrap();
+        assert_eq!(reflection.entry_point, "main");
+        assert_eq!(reflection.stage, ShaderStageFlags::COMPUTE);
+    }
+
+    #[test]
+    fn reads_the_descriptors() {
+        let reflection = ShaderReflection::from_spirv(&load_test_shader()).unwrap();
+        assert_eq!(
+            reflection.bindings,
+            [
+                binding("_Framebuffer", 0, 0, DescriptorType::STORAGE_IMAGE, 1),
+                binding("_Triangles", 0, 1, DescriptorType::STORAGE_BUFFER, 1),
+                binding("_Camera", 0, 4, DescriptorType::UNIFORM_BUFFER, 1),
+                binding("_Textures", 1, 0, DescriptorType::SAMPLED_IMAGE, 8),
+                binding("_Sampler", 1, 1, DescriptorType::SAMPLER, 1),
+            ]
+        );
+        assert_eq!(reflection.get_set_count(), 2);
+        assert_eq!(reflection.find_variable_binding(1), None);
+    }
+
+    #[test]
+    fn reads_the_push_constants_size() {
+        let reflection = ShaderReflection::from_spirv(&load_test_shader()).unwrap();
+        let ranges = reflection.get_push_constant_ranges();
+        assert_eq!(ranges.len(), 1);
+        // a uvec4 then two uint
+        assert_eq!(ranges[0].size, 24);
+        assert_eq!(ranges[0].stage_flags, ShaderStageFlags::COMPUTE);
+    }
+
+    #[test]
+    fn counts_the_descriptors_of_each_type() {
+        let reflection = ShaderReflection::from_spirv(&load_test_shader()).unwrap();
+        let sampled_images = reflection
+            .get_pool_size_ratios(&[])
+            .into_iter()
+            .find(|ratio| ratio.descriptor_type == DescriptorType::SAMPLED_IMAGE)
+            .unwrap();
+        // 8 images in one of the 2 sets
+        assert_eq!(sampled_images.ratio * 2., 8.);
+    }
+
+    #[test]
+    fn counts_the_descriptors_of_the_arrays_without_size() {
+        let reflection = ShaderReflection {
+            bindings: vec![
+                binding("_Camera", 0, 0, DescriptorType::UNIFORM_BUFFER, 1),
+                binding("_Textures", 0, 1, DescriptorType::SAMPLED_IMAGE, 0),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            reflection
+                .find_variable_binding(0)
+                .map(|binding| binding.binding),
+            Some(1)
+        );
+        let ratios = reflection.get_pool_size_ratios(&[100]);
+        assert_eq!(ratios.len(), 2);
+        assert!(ratios.iter().any(
+            |ratio| ratio.descriptor_type == DescriptorType::SAMPLED_IMAGE && ratio.ratio == 100.
+        ));
+    }
+
+    #[test]
+    fn rejects_other_data() {
+        assert!(ShaderReflection::from_spirv(&[0; 8]).is_err());
+    }
+}
@@ -1,16 +1,32 @@
 use ash::{
     vk::{
+        self, Buffer, DescriptorBindingFlags, DescriptorBufferInfo, DescriptorImageInfo,
         DescriptorPool, DescriptorPoolCreateInfo, DescriptorPoolResetFlags, DescriptorPoolSize,
         DescriptorSet, DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding,
-        DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType,
-        ShaderStageFlags,
+        DescriptorSetLayoutBindingFlagsCreateInfo, DescriptorSetLayoutCreateFlags,
+        DescriptorSetLayoutCreateInfo, DescriptorSetVariableDescriptorCountAllocateInfo,
+        DescriptorType, ImageLayout, ImageView, Sampler, ShaderStageFlags, WriteDescriptorSet,
+        WHOLE_SIZE,
     },
     Device,
 };
 
+use crate::application::error::{Error, Result};
+
+use super::spirv_reflection::{ReflectedBinding, ShaderReflection};
+
+/// Number of descriptors in the layout of a shader array declared without a size
+/// The sets allocate the number of descriptors they use, up to this one
+pub const MAX_VARIABLE_DESCRIPTOR_COUNT: u32 = 1024;
+
+/// Largest number of sets of the pools created when the previous ones are full
+const MAX_SETS_PER_POOL: u32 = 4092;
+
 #[derive(Default)]
 pub struct DescriptorLayoutBuilder<'a> {
     pub bindings: Vec<DescriptorSetLayoutBinding<'a>>,
+    /// Flags of each binding, in the same order
+    pub binding_flags: Vec<DescriptorBindingFlags>,
 }
 
 impl<'a> DescriptorLayoutBuilder<'a> {
@@ -20,6 +36,56 @@ impl<'a> DescriptorLayoutBuilder<'a> {
             .descriptor_count(1)
             .descriptor_type(descriptor_type);
         self.bindings.push(new_bind);
+        self.binding_flags.push(DescriptorBindingFlags::empty());
+    }
+
+    /// Adds an array whose sets choose the number of descriptors when they are allocated
+    /// It must be the last binding of the set
+    pub fn add_variable_binding(&mut self, binding: u32, descriptor_type: DescriptorType) {
+        let new_bind = DescriptorSetLayoutBinding::default()
+            .binding(binding)
+            .descriptor_count(MAX_VARIABLE_DESCRIPTOR_COUNT)
+            .descriptor_type(descriptor_type);
+        self.bindings.push(new_bind);
+        // the descriptors after the count of the set are never written
+        self.binding_flags.push(
+            DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT
+                | DescriptorBindingFlags::PARTIALLY_BOUND,
+        );
+    }
+
+    /// Adds the bindings of a descriptor set declared by a shader
+    /// The arrays declared without a size become variable count bindings
+    pub fn add_reflected_bindings(
+        &mut self,
+        reflection: &ShaderReflection,
+        set: u32,
+    ) -> Result<()> {
+        let variable_binding = reflection.find_variable_binding(set);
+        for binding in reflection
+            .bindings
+            .iter()
+            .filter(|binding| binding.set == set)
+        {
+            if binding.descriptor_count != 0 {
+                self.bindings.push(
+                    DescriptorSetLayoutBinding::default()
+                        .binding(binding.binding)
+                        .descriptor_type(binding.descriptor_type)
+                        .descriptor_count(binding.descriptor_count)
+                        .stage_flags(reflection.stage),
+                );
+                self.binding_flags.push(DescriptorBindingFlags::empty());
+            } else if variable_binding == Some(binding) {
+                self.add_variable_binding(binding.binding, binding.descriptor_type);
+            } else {
+                return Err(Error::ShaderReflection(format!(
+                    "{} is an array without size, only the last binding of a set can be one",
+                    binding.name
+                )));
+            }
+        }
+        Ok(())
     }
 
     pub fn build(
@@ -27,42 +93,74 @@ impl<'a> DescriptorLayoutBuilder<'a> {
         device: &Device,
         shader_stages: ShaderStageFlags,
         flags: DescriptorSetLayoutCreateFlags,
-    ) -> DescriptorSetLayout {
+    ) -> Result<DescriptorSetLayout> {
         self.bindings.iter_mut().for_each(|binding| {
             binding.stage_flags |= shader_stages;
         });
 
-        let create_info = DescriptorSetLayoutCreateInfo::default()
+        let mut binding_flags_create_info =
+            DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(&self.binding_flags);
+        let mut create_info = DescriptorSetLayoutCreateInfo::default()
             .bindings(&self.bindings)
             .flags(flags);
+        // the flags of the descriptor indexing are only chained when a binding uses them
+        if self.binding_flags.iter().any(|flags| !flags.is_empty()) {
+            create_info = create_info.push_next(&mut binding_flags_create_info);
+        }
 
         unsafe {
             device
                 .create_descriptor_set_layout(&create_info, None)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to create the descriptor set layout"))
         }
     }
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct PoolSizeRatio {
     pub descriptor_type: DescriptorType,
     pub ratio: f32,
 }
 
+/// Allocates descriptor sets from a list of pools
+/// When the pools are full, a larger one is created with the same ratios of descriptors per set
 #[derive(Default)]
 pub struct DescriptorAllocator {
-    pub descriptor_pool: DescriptorPool,
+    pool_ratios: Vec<PoolSizeRatio>,
+    /// The pools that may have room for another set
+    ready_pools: Vec<DescriptorPool>,
+    /// The pools that failed an allocation, reused after a reset
+    full_pools: Vec<DescriptorPool>,
+    /// Number of sets of the next pool
+    sets_per_pool: u32,
 }
 
 impl DescriptorAllocator {
-    pub fn init_pool(&mut self, device: &Device, max_sets: u32, pool_ratios: &[PoolSizeRatio]) {
-        let pool_sizes = pool_ratios
+    /// Creates the first pool, sized for `max_sets` sets
+    pub fn init_pool(
+        &mut self,
+        device: &Device,
+        max_sets: u32,
+        pool_ratios: &[PoolSizeRatio],
+    ) -> Result<()> {
+        self.pool_ratios = pool_ratios.to_vec();
+        self.sets_per_pool = max_sets.max(1);
+        let descriptor_pool = self.create_pool(device)?;
+        self.ready_pools.push(descriptor_pool);
+        Ok(())
+    }
+
+    /// Creates a pool of `sets_per_pool` sets, the next one is larger
+    fn create_pool(&mut self, device: &Device) -> Result<DescriptorPool> {
+        let max_sets = self.sets_per_pool;
+        let pool_sizes = self
+            .pool_ratios
             .iter()
             .map(|ratio| {
+                // a pool size cannot be empty
                 DescriptorPoolSize::default()
                     .ty(ratio.descriptor_type)
-                    .descriptor_count((ratio.ratio * (max_sets as f32)) as u32)
+                    .descriptor_count(((ratio.ratio * (max_sets as f32)).ceil() as u32).max(1))
             })
             .collect::<Vec<DescriptorPoolSize>>();
 
@@ -70,24 +168,46 @@ impl DescriptorAllocator {
             .max_sets(max_sets)
             .pool_sizes(&pool_sizes);
 
-        unsafe {
-            self.descriptor_pool = device
+        let descriptor_pool = unsafe {
+            device
                 .create_descriptor_pool(&pool_create_info, None)
-                .unwrap();
+                .map_err(Error::vulkan("Failed to create the descriptor pool"))?
+        };
+        self.sets_per_pool = get_next_sets_per_pool(max_sets);
+        Ok(descriptor_pool)
+    }
+
+    fn get_pool(&mut self, device: &Device) -> Result<DescriptorPool> {
+        match self.ready_pools.pop() {
+            Some(descriptor_pool) => Ok(descriptor_pool),
+            None => self.create_pool(device),
         }
     }
 
-    pub fn clear_descriptors(&mut self, device: &Device) {
-        unsafe {
-            device
-                .reset_descriptor_pool(self.descriptor_pool, DescriptorPoolResetFlags::empty())
-                .unwrap();
+    /// Frees every set allocated from the pools, to reuse them
+    /// The transient sets of a frame are reset once its fence is signaled
+    pub fn reset_pools(&mut self, device: &Device) -> Result<()> {
+        for &descriptor_pool in self.ready_pools.iter().chain(&self.full_pools) {
+            unsafe {
+                device
+                    .reset_descriptor_pool(descriptor_pool, DescriptorPoolResetFlags::empty())
+                    .map_err(Error::vulkan("Failed to reset the descriptor pool"))?;
+            }
         }
+        self.reuse_full_pools();
+        Ok(())
     }
 
-    pub fn clear_pool(&mut self, device: &Device) {
-        unsafe {
-            device.destroy_descriptor_pool(self.descriptor_pool, None);
+    /// The full pools are empty again once they are reset
+    fn reuse_full_pools(&mut self) {
+        self.ready_pools.append(&mut self.full_pools);
+    }
+
+    pub fn destroy_pools(&mut self, device: &Device) {
+        for descriptor_pool in self.ready_pools.drain(..).chain(self.full_pools.drain(..)) {
+            unsafe {
+                device.destroy_descriptor_pool(descriptor_pool, None);
+            }
         }
     }
 
@@ -95,11 +215,290 @@ impl DescriptorAllocator {
         &mut self,
         device: &Device,
         descriptor_set_layout: &DescriptorSetLayout,
-    ) -> DescriptorSet {
+    ) -> Result<DescriptorSet> {
+        self.allocate_from_pools(device, descriptor_set_layout, None)
+    }
+
+    /// Allocates a set whose layout ends with a variable count binding, with `variable_count`
+    /// descriptors in it
+    pub fn allocate_variable(
+        &mut self,
+        device: &Device,
+        descriptor_set_layout: &DescriptorSetLayout,
+        variable_count: u32,
+    ) -> Result<DescriptorSet> {
+        self.allocate_from_pools(device, descriptor_set_layout, Some(variable_count))
+    }
+
+    /// Allocates from the last ready pool, and from a new one when it is full
+    fn allocate_from_pools(
+        &mut self,
+        device: &Device,
+        descriptor_set_layout: &DescriptorSetLayout,
+        variable_count: Option<u32>,
+    ) -> Result<DescriptorSet> {
+        let mut descriptor_pool = self.get_pool(device)?;
+        let mut result = Self::allocate_from(
+            device,
+            descriptor_pool,
+            descriptor_set_layout,
+            variable_count,
+        );
+
+        if let Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL) =
+            result
+        {
+            self.full_pools.push(descriptor_pool);
+            descriptor_pool = self.get_pool(device)?;
+            result = Self::allocate_from(
+                device,
+                descriptor_pool,
+                descriptor_set_layout,
+                variable_count,
+            );
+        }
+        self.ready_pools.push(descriptor_pool);
+        result.map_err(Error::vulkan("Failed to allocate the descriptor set"))
+    }
+
+    fn allocate_from(
+        device: &Device,
+        descriptor_pool: DescriptorPool,
+        descriptor_set_layout: &DescriptorSetLayout,
+        variable_count: Option<u32>,
+    ) -> std::result::Result<DescriptorSet, vk::Result> {
         let layouts = [*descriptor_set_layout];
-        let allocate_info = DescriptorSetAllocateInfo::default()
-            .descriptor_pool(self.descriptor_pool)
+        let variable_counts = [variable_count.unwrap_or(0)];
+        let mut variable_count_allocate_info =
+            DescriptorSetVariableDescriptorCountAllocateInfo::default()
+                .descriptor_counts(&variable_counts);
+        let mut allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(descriptor_pool)
             .set_layouts(&layouts);
-        unsafe { device.allocate_descriptor_sets(&allocate_info).unwrap()[0] }
+        if variable_count.is_some() {
+            allocate_info = allocate_info.push_next(&mut variable_count_allocate_info);
+        }
+
+        let descriptor_sets = unsafe { device.allocate_descriptor_sets(&allocate_info)? };
+        Ok(descriptor_sets[0])
+    }
+}
+
+/// Grows the pools by half, capped at `MAX_SETS_PER_POOL`
+fn get_next_sets_per_pool(sets_per_pool: u32) -> u32 {
+    (sets_per_pool * 3 / 2)
+        .max(sets_per_pool + 1)
+        .min(MAX_SETS_PER_POOL)
+}
+
+/// Collects the resources of the descriptors of a shader by the name of their parameter,
+/// then writes them in the descriptor sets allocated for the shader
+pub struct DescriptorWriter<'a> {
+    reflection: &'a ShaderReflection,
+    buffer_writes: Vec<(&'a ReflectedBinding, DescriptorBufferInfo)>,
+    /// The arrays of descriptors are written from their first element
+    image_writes: Vec<(&'a ReflectedBinding, Vec<DescriptorImageInfo>)>,
+}
+
+impl<'a> DescriptorWriter<'a> {
+    pub fn new(reflection: &'a ShaderReflection) -> Self {
+        Self {
+            reflection,
+            buffer_writes: Vec::new(),
+            image_writes: Vec::new(),
+        }
+    }
+
+    /// Finds the binding of a parameter, checking it holds the expected kind of resource
+    fn find_binding(
+        &self,
+        name: &str,
+        is_expected_type: fn(DescriptorType) -> bool,
+    ) -> Result<&'a ReflectedBinding> {
+        let binding = self.reflection.find_binding(name).ok_or_else(|| {
+            Error::ShaderReflection(format!(
+                "The entry point {} has no descriptor named {}",
+                self.reflection.entry_point, name
+            ))
+        })?;
+        if !is_expected_type(binding.descriptor_type) {
+            return Err(Error::ShaderReflection(format!(
+                "The descriptor {} cannot hold this resource, it is a {:?}",
+                name, binding.descriptor_type
+            )));
+        }
+        Ok(binding)
+    }
+
+    pub fn write_buffer(&mut self, name: &str, buffer: Buffer) -> Result<()> {
+        let binding = self.find_binding(name, |descriptor_type| {
+            matches!(
+                descriptor_type,
+                DescriptorType::STORAGE_BUFFER | DescriptorType::UNIFORM_BUFFER
+            )
+        })?;
+        let buffer_info = DescriptorBufferInfo::default()
+            .buffer(buffer)
+            .range(WHOLE_SIZE)
+            .offset(0);
+        self.buffer_writes.push((binding, buffer_info));
+        Ok(())
+    }
+
+    pub fn write_image(
+        &mut self,
+        name: &str,
+        image_view: ImageView,
+        image_layout: ImageLayout,
+    ) -> Result<()> {
+        let binding = self.find_binding(name, |descriptor_type| {
+            matches!(
+                descriptor_type,
+                DescriptorType::STORAGE_IMAGE | DescriptorType::SAMPLED_IMAGE
+            )
+        })?;
+        let image_info = DescriptorImageInfo::default()
+            .image_view(image_view)
+            .image_layout(image_layout);
+        self.image_writes.push((binding, vec![image_info]));
+        Ok(())
+    }
+
+    /// Writes the images in an array of sampled images, an empty array is left unbound
+    pub fn write_images(
+        &mut self,
+        name: &str,
+        image_views: &[ImageView],
+        image_layout: ImageLayout,
+    ) -> Result<()> {
+        let binding = self.find_binding(name, |descriptor_type| {
+            descriptor_type == DescriptorType::SAMPLED_IMAGE
+        })?;
+        if binding.descriptor_count != 0 && image_views.len() > binding.descriptor_count as usize {
+            return Err(Error::ShaderReflection(format!(
+                "The descriptor {} holds {} images, not {}",
+                name,
+                binding.descriptor_count,
+                image_views.len()
+            )));
+        }
+        if image_views.is_empty() {
+            return Ok(());
+        }
+        let image_infos = image_views
+            .iter()
+            .map(|&image_view| {
+                DescriptorImageInfo::default()
+                    .image_view(image_view)
+                    .image_layout(image_layout)
+            })
+            .collect();
+        self.image_writes.push((binding, image_infos));
+        Ok(())
+    }
+
+    pub fn write_sampler(&mut self, name: &str, sampler: Sampler) -> Result<()> {
+        let binding = self.find_binding(name, |descriptor_type| {
+            descriptor_type == DescriptorType::SAMPLER
+        })?;
+        let sampler_info = DescriptorImageInfo::default().sampler(sampler);
+        self.image_writes.push((binding, vec![sampler_info]));
+        Ok(())
+    }
+
+    /// Writes the resources in the sets, indexed by the set numbers of the shader
+    pub fn update_sets(&self, device: &Device, descriptor_sets: &[DescriptorSet]) {
+        let buffer_writes = self.buffer_writes.iter().map(|(binding, buffer_info)| {
+            WriteDescriptorSet::default()
+                .dst_set(descriptor_sets[binding.set as usize])
+                .dst_binding(binding.binding)
+                .descriptor_type(binding.descriptor_type)
+                .buffer_info(std::slice::from_ref(buffer_info))
+        });
+        let image_writes = self.image_writes.iter().map(|(binding, image_infos)| {
+            WriteDescriptorSet::default()
+                .dst_set(descriptor_sets[binding.set as usize])
+                .dst_binding(binding.binding)
+                .descriptor_type(binding.descriptor_type)
+                .image_info(image_infos)
+        });
+        let descriptor_writes: Vec<WriteDescriptorSet> =
+            buffer_writes.chain(image_writes).collect();
+
+        unsafe {
+            device.update_descriptor_sets(&descriptor_writes, &[]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ash::vk::{self, DescriptorType, Handle};
+
+    use super::{
+        get_next_sets_per_pool, DescriptorAllocator, DescriptorLayoutBuilder, MAX_SETS_PER_POOL,
+    };
+    use crate::application::pipelines::spirv_reflection::{ReflectedBinding, ShaderReflection};
+
+    fn binding(name: &str, binding: u32, descriptor_count: u32) -> ReflectedBinding {
+        ReflectedBinding {
+            name: name.to_owned(),
+            set: 0,
+            binding,
+            descriptor_type: DescriptorType::SAMPLED_IMAGE,
+            descriptor_count,
+        }
+    }
+
+    #[test]
+    fn pools_grow_until_the_limit() {
+        assert_eq!(get_next_sets_per_pool(1), 2);
+        assert_eq!(get_next_sets_per_pool(2), 3);
+        assert_eq!(get_next_sets_per_pool(10), 15);
+
+        let mut sets_per_pool = 1;
+        for _ in 0..100 {
+            let next_sets_per_pool = get_next_sets_per_pool(sets_per_pool);
+            assert!(next_sets_per_pool > sets_per_pool || sets_per_pool == MAX_SETS_PER_POOL);
+            sets_per_pool = next_sets_per_pool;
+        }
+        assert_eq!(sets_per_pool, MAX_SETS_PER_POOL);
+        assert_eq!(get_next_sets_per_pool(MAX_SETS_PER_POOL), MAX_SETS_PER_POOL);
+    }
+
+    #[test]
+    fn only_the_last_binding_is_an_array_without_size() {
+        let reflection = ShaderReflection {
+            bindings: vec![binding("_Textures", 0, 0), binding("_Normals", 1, 4)],
+            ..Default::default()
+        };
+        let mut builder = DescriptorLayoutBuilder::default();
+        assert!(builder.add_reflected_bindings(&reflection, 0).is_err());
+
+        let reflection = ShaderReflection {
+            bindings: vec![binding("_Normals", 0, 4), binding("_Textures", 1, 0)],
+            ..Default::default()
+        };
+        let mut builder = DescriptorLayoutBuilder::default();
+        builder.add_reflected_bindings(&reflection, 0).unwrap();
+        assert_eq!(builder.bindings.len(), 2);
+        assert!(!builder.binding_flags[1].is_empty());
+    }
+
+    #[test]
+    fn reset_pools_are_ready_again() {
+        let pool = |handle| vk::DescriptorPool::from_raw(handle);
+        let mut allocator = DescriptorAllocator {
+            ready_pools: vec![pool(1)],
+            full_pools: vec![pool(2), pool(3)],
+            ..Default::default()
+        };
+        allocator.reuse_full_pools();
+        assert_eq!(allocator.ready_pools, [pool(1), pool(2), pool(3)]);
+        assert!(allocator.full_pools.is_empty());
+
+        // nothing is lost when no pool is full
+        allocator.reuse_full_pools();
+        assert_eq!(allocator.ready_pools.len(), 3);
     }
 }
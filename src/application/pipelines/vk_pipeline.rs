@@ -1,13 +1,14 @@
-use crate::application::vk_app::VulkanApp;
+use crate::application::{error::Result, vk_app::VulkanApp};
 
 use super::{pipeline::ComputePipeline, pipeline_raytracing::PipelineRaytracing};
 
 impl VulkanApp {
-    pub fn init_pipelines(&mut self) {
+    pub fn init_pipelines(&mut self) -> Result<()> {
         let mut gradient_pipeline: PipelineRaytracing = Default::default();
-        gradient_pipeline.init(self);
+        gradient_pipeline.init(self)?;
 
-        self.pipelines = vec![Box::new(gradient_pipeline)]
+        self.pipelines = vec![Box::new(gradient_pipeline)];
+        Ok(())
     }
 
     pub fn clear_pipelines(&mut self) {
@@ -0,0 +1,180 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, warn};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::application::{
+    error::{Error, Result},
+    vk_app::VulkanApp,
+};
+
+use super::pipeline::{ComputePipeline, PipelineUtils};
+
+/// Time without a new change before the shaders are recompiled
+/// The editors often save a file in several writes
+const SHADER_CHANGE_DELAY: Duration = Duration::from_millis(100);
+
+/// Watches the Slang files of the shader source directory
+pub struct ShaderWatcher {
+    // the files are no longer watched once it is dropped
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+    changed_files: HashSet<PathBuf>,
+    last_change: Option<Instant>,
+}
+
+impl ShaderWatcher {
+    pub fn new(shader_directory: &Path) -> notify::Result<Self> {
+        let (sender, receiver) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(shader_directory, RecursiveMode::Recursive)?;
+        Ok(Self {
+            _watcher: watcher,
+            receiver,
+            changed_files: HashSet::new(),
+            last_change: None,
+        })
+    }
+
+    /// Returns the Slang files changed since the last call, once they stopped changing
+    pub fn take_changed_files(&mut self) -> HashSet<PathBuf> {
+        for event in self.receiver.try_iter() {
+            match event {
+                Ok(event) if event.kind.is_create() || event.kind.is_modify() => {
+                    let slang_files = event.paths.into_iter().filter(|path| {
+                        path.extension()
+                            .is_some_and(|extension| extension == "slang")
+                    });
+                    for path in slang_files {
+                        self.changed_files.insert(path);
+                        self.last_change = Some(Instant::now());
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => warn!("Failed to watch the shaders: {}", err),
+            }
+        }
+
+        match self.last_change {
+            Some(last_change) if last_change.elapsed() >= SHADER_CHANGE_DELAY => {
+                self.last_change = None;
+                std::mem::take(&mut self.changed_files)
+            }
+            _ => HashSet::new(),
+        }
+    }
+}
+
+impl VulkanApp {
+    /// Starts watching the shader sources, the hot reload is disabled when they cannot be watched,
+    /// when there is no source directory or no shader directory to write the compiled shaders
+    pub fn init_shader_watcher(&mut self) {
+        if self.app_params.shader_directory.is_none() {
+            debug!("The shaders will not be reloaded without a shader directory");
+            return;
+        }
+        let Some(shader_source_directory) = &self.app_params.shader_source_directory else {
+            debug!("The shaders will not be reloaded without a shader source directory");
+            return;
+        };
+        match ShaderWatcher::new(shader_source_directory) {
+            Ok(shader_watcher) => self.shader_watcher = Some(shader_watcher),
+            Err(err) => warn!(
+                "The shaders will not be reloaded, failed to watch {:?}: {}",
+                shader_source_directory, err
+            ),
+        }
+    }
+
+    /// Recompiles the changed shaders and recreates the pipelines using them
+    /// Must be called between two frames. On a compilation error the previous pipeline
+    /// keeps running and the error is shown in the GUI until the next successful reload.
+    pub fn reload_shaders(&mut self) -> Result<()> {
+        let Some(shader_watcher) = self.shader_watcher.as_mut() else {
+            return Ok(());
+        };
+        let changed_files = shader_watcher.take_changed_files();
+        if changed_files.is_empty() {
+            return Ok(());
+        }
+        debug!("Shaders changed: {:?}", changed_files);
+
+        // Take the pipelines out of self temporarily
+        let mut pipelines = std::mem::take(&mut self.pipelines);
+        let result = self.reload_pipelines(&mut pipelines, &changed_files);
+        // Put the pipelines back into self
+        self.pipelines = pipelines;
+        result
+    }
+
+    fn reload_pipelines(
+        &mut self,
+        pipelines: &mut [Box<dyn ComputePipeline>],
+        changed_files: &HashSet<PathBuf>,
+    ) -> Result<()> {
+        // the modules imported by a shader are not tracked, a changed module reloads every pipeline
+        let is_module_changed = changed_files.iter().any(|changed_file| {
+            !pipelines
+                .iter()
+                .any(|pipeline| changed_file.ends_with(pipeline.get_shader_source().file))
+        });
+
+        let (Some(shader_source_directory), Some(shader_directory)) = (
+            self.app_params.shader_source_directory.clone(),
+            self.app_params.shader_directory.clone(),
+        ) else {
+            return Ok(());
+        };
+        let mut errors = Vec::new();
+        let mut is_reloaded = false;
+        for pipeline in pipelines.iter_mut() {
+            let shader_source = pipeline.get_shader_source();
+            let is_affected = is_module_changed
+                || changed_files
+                    .iter()
+                    .any(|changed_file| changed_file.ends_with(shader_source.file));
+            if !is_affected {
+                continue;
+            }
+
+            if let Err(err) = PipelineUtils::compile_shader(
+                &shader_source,
+                &shader_source_directory,
+                &shader_directory,
+            ) {
+                errors.push(err);
+                continue;
+            }
+            // the previous pipeline may still be used by the frames in flight
+            unsafe {
+                self.device
+                    .device_wait_idle()
+                    .map_err(Error::vulkan("Failed to wait for the device"))?;
+            }
+            match pipeline.reload(self) {
+                Ok(()) => is_reloaded = true,
+                Err(err) => errors.push(err),
+            }
+        }
+
+        if is_reloaded {
+            self.reset_accumulation();
+        }
+        if errors.is_empty() {
+            debug!("Shaders reloaded");
+            self.shader_error = None;
+        } else {
+            let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            for message in &messages {
+                error!("{}", message);
+            }
+            self.shader_error = Some(messages.join("\n"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use super::{
+    bvh::{Aabb, Bvh, BvhNodeGPU, BvhParameters},
+    mesh::Mesh,
+    model::Model,
+    triangle::Triangle,
+};
+
+/// Bottom level bvhs of every mesh in object space, concatenated in shared buffers
+/// Leaves reference the scene triangles
+#[derive(Default)]
+pub struct BottomLevelAccelerationStructure {
+    pub nodes: Vec<BvhNodeGPU>,
+    pub triangle_indices: Vec<u32>,
+    /// Index of the root node of each mesh
+    pub roots: Vec<u32>,
+    /// Object space bounds of each mesh
+    pub aabbs: Vec<Aabb>,
+}
+
+/// Bvh over the world space bounds of the models
+/// Leaves reference the scene models
+#[derive(Default)]
+pub struct TopLevelAccelerationStructure {
+    pub bvh: Bvh,
+}
+
+impl BottomLevelAccelerationStructure {
+    pub fn build(triangles: &[Triangle], meshes: &[Mesh], parameters: &BvhParameters) -> Self {
+        let mut blas = Self::default();
+
+        for mesh in meshes {
+            let mesh_triangles =
+                &triangles[mesh.first_triangle..mesh.first_triangle + mesh.nb_triangles];
+            let aabbs: Vec<Aabb> = mesh_triangles.iter().map(Bvh::get_triangle_aabb).collect();
+            let bvh = Bvh::build(&aabbs, parameters);
+
+            // the node and primitive indices are offset to index the shared buffers
+            let node_offset = blas.nodes.len() as u32;
+            let index_offset = blas.triangle_indices.len() as u32;
+            blas.nodes.extend(bvh.nodes.iter().map(|node| {
+                let mut node = *node;
+                if node.is_leaf() {
+                    node.left_or_first += index_offset;
+                } else if node.left_or_first > 0 {
+                    node.left_or_first += node_offset;
+                }
+                node
+            }));
+            blas.triangle_indices.extend(
+                bvh.primitive_indices
+                    .iter()
+                    .map(|&index| index + mesh.first_triangle as u32),
+            );
+            blas.roots.push(node_offset);
+            blas.aabbs.push(bvh.nodes[0].get_aabb());
+        }
+
+        blas
+    }
+}
+
+impl TopLevelAccelerationStructure {
+    /// Gets the world space bounds of every model
+    pub fn get_model_aabbs(models: &[Model], blas: &BottomLevelAccelerationStructure) -> Vec<Aabb> {
+        models
+            .iter()
+            .map(|model| blas.aabbs[model.mesh_index].transform(&model.model_matrix))
+            .collect()
+    }
+
+    pub fn build(
+        models: &[Model],
+        blas: &BottomLevelAccelerationStructure,
+        parameters: &BvhParameters,
+    ) -> Self {
+        let aabbs = Self::get_model_aabbs(models, blas);
+        Self {
+            bvh: Bvh::build(&aabbs, parameters),
+        }
+    }
+}
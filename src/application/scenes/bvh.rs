@@ -0,0 +1,550 @@
+use glam::{Mat4, Vec3, Vec4Swizzles};
+use gpu_layout::GpuLayout;
+
+use super::triangle::Triangle;
+
+/// Axis aligned bounding box
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    /// An empty box, growing it by any point gives a valid box
+    fn default() -> Self {
+        Self {
+            min: Vec3::splat(f32::INFINITY),
+            max: Vec3::splat(f32::NEG_INFINITY),
+        }
+    }
+}
+
+impl Aabb {
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn grow(&mut self, point: Vec3) {
+        self.min = self.min.min(point);
+        self.max = self.max.max(point);
+    }
+
+    pub fn grow_aabb(&mut self, other: &Aabb) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.;
+        }
+        let extent = self.extent();
+        2. * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    /// Gets the box containing this box transformed by the given matrix
+    pub fn transform(&self, matrix: &Mat4) -> Aabb {
+        let mut aabb = Aabb::default();
+        if self.is_empty() {
+            return aabb;
+        }
+        for corner in 0..8 {
+            let point = Vec3::new(
+                if corner & 1 == 0 {
+                    self.min.x
+                } else {
+                    self.max.x
+                },
+                if corner & 2 == 0 {
+                    self.min.y
+                } else {
+                    self.max.y
+                },
+                if corner & 4 == 0 {
+                    self.min.z
+                } else {
+                    self.max.z
+                },
+            );
+            aabb.grow(matrix.transform_point3(point));
+        }
+        aabb
+    }
+
+    pub fn contains(&self, other: &Aabb, epsilon: f32) -> bool {
+        other.is_empty()
+            || ((self.min - epsilon).cmple(other.min).all()
+                && (self.max + epsilon).cmpge(other.max).all())
+    }
+}
+
+/// A node of the flattened bvh
+/// The layout matches the `BvhNode` struct in `scene/bvh.slang`
+/// Internal nodes have a `primitive_count` of 0 and their children at
+/// `left_or_first` and `left_or_first + 1`, leaves reference `primitive_count`
+/// primitives starting at `left_or_first` in the primitive indices
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, GpuLayout)]
+pub struct BvhNodeGPU {
+    pub aabb_min: Vec3,
+    pub left_or_first: u32,
+    pub aabb_max: Vec3,
+    pub primitive_count: u32,
+}
+
+impl BvhNodeGPU {
+    pub fn is_leaf(&self) -> bool {
+        self.primitive_count > 0
+    }
+
+    pub fn get_aabb(&self) -> Aabb {
+        Aabb {
+            min: self.aabb_min,
+            max: self.aabb_max,
+        }
+    }
+}
+
+pub struct BvhParameters {
+    /// Nodes with at most this number of primitives are not split
+    pub max_leaf_size: usize,
+    /// Nodes at this depth are not split
+    pub max_depth: usize,
+    /// Number of bins per axis used to evaluate the SAH
+    pub nb_bins: usize,
+}
+
+impl Default for BvhParameters {
+    fn default() -> Self {
+        Self {
+            max_leaf_size: 4,
+            max_depth: 32,
+            nb_bins: 16,
+        }
+    }
+}
+
+/// Bounding volume hierarchy over a list of primitives
+#[derive(Default)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNodeGPU>,
+    pub primitive_indices: Vec<u32>,
+}
+
+/// Per primitive data used during the build
+struct BuildPrimitive {
+    aabb: Aabb,
+    centroid: Vec3,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Bin {
+    aabb: Aabb,
+    count: usize,
+}
+
+/// A split plane between two bins along an axis
+#[derive(Clone, Copy)]
+struct Split {
+    axis: usize,
+    bin_index: usize,
+    bounds_min: f32,
+    scale: f32,
+    cost: f32,
+}
+
+impl Split {
+    /// Primitives whose centroid are in bins before the plane go to the left child
+    fn is_left(&self, centroid: Vec3, nb_bins: usize) -> bool {
+        let bin_index =
+            (((centroid[self.axis] - self.bounds_min) * self.scale) as usize).min(nb_bins - 1);
+        bin_index <= self.bin_index
+    }
+}
+
+impl Bvh {
+    /// Gets the object space bounding box of a triangle
+    pub fn get_triangle_aabb(triangle: &Triangle) -> Aabb {
+        let mut aabb = Aabb::default();
+        for point in [triangle.p0, triangle.p1, triangle.p2] {
+            aabb.grow(point.xyz());
+        }
+        aabb
+    }
+
+    /// Builds a bvh over the bounding boxes of the primitives
+    pub fn build(aabbs: &[Aabb], parameters: &BvhParameters) -> Self {
+        let build_primitives: Vec<BuildPrimitive> = aabbs
+            .iter()
+            .map(|aabb| BuildPrimitive {
+                aabb: *aabb,
+                centroid: 0.5 * (aabb.min + aabb.max),
+            })
+            .collect();
+
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * aabbs.len().max(1)),
+            primitive_indices: (0..aabbs.len() as u32).collect(),
+        };
+
+        bvh.nodes.push(BvhNodeGPU {
+            left_or_first: 0,
+            primitive_count: aabbs.len() as u32,
+            ..Default::default()
+        });
+        bvh.update_node_bounds(0, &build_primitives);
+        bvh.subdivide(0, 0, &build_primitives, parameters);
+
+        bvh
+    }
+
+    fn update_node_bounds(&mut self, node_index: usize, build_primitives: &[BuildPrimitive]) {
+        let node = &mut self.nodes[node_index];
+        let first = node.left_or_first as usize;
+        let count = node.primitive_count as usize;
+
+        let mut aabb = Aabb::default();
+        for &primitive_index in &self.primitive_indices[first..first + count] {
+            aabb.grow_aabb(&build_primitives[primitive_index as usize].aabb);
+        }
+
+        node.aabb_min = aabb.min;
+        node.aabb_max = aabb.max;
+    }
+
+    /// Finds the best split using binned SAH
+    fn find_best_split(
+        &self,
+        node: &BvhNodeGPU,
+        build_primitives: &[BuildPrimitive],
+        nb_bins: usize,
+    ) -> Option<Split> {
+        let first = node.left_or_first as usize;
+        let count = node.primitive_count as usize;
+        let node_primitives = &self.primitive_indices[first..first + count];
+
+        let mut centroid_aabb = Aabb::default();
+        for &primitive_index in node_primitives {
+            centroid_aabb.grow(build_primitives[primitive_index as usize].centroid);
+        }
+
+        let mut best_split: Option<Split> = None;
+        for axis in 0..3 {
+            let bounds_min = centroid_aabb.min[axis];
+            let bounds_max = centroid_aabb.max[axis];
+            if bounds_min == bounds_max {
+                continue;
+            }
+
+            // fill the bins
+            let mut bins = vec![Bin::default(); nb_bins];
+            let scale = nb_bins as f32 / (bounds_max - bounds_min);
+            for &primitive_index in node_primitives {
+                let build_primitive = &build_primitives[primitive_index as usize];
+                let bin_index = (((build_primitive.centroid[axis] - bounds_min) * scale) as usize)
+                    .min(nb_bins - 1);
+                bins[bin_index].count += 1;
+                bins[bin_index].aabb.grow_aabb(&build_primitive.aabb);
+            }
+
+            // sweep from both sides to get the areas and counts of each split plane
+            let mut left_areas = vec![0.; nb_bins - 1];
+            let mut left_counts = vec![0; nb_bins - 1];
+            let mut right_areas = vec![0.; nb_bins - 1];
+            let mut right_counts = vec![0; nb_bins - 1];
+            let mut left_aabb = Aabb::default();
+            let mut right_aabb = Aabb::default();
+            let mut left_count = 0;
+            let mut right_count = 0;
+            for i in 0..nb_bins - 1 {
+                left_count += bins[i].count;
+                left_aabb.grow_aabb(&bins[i].aabb);
+                left_counts[i] = left_count;
+                left_areas[i] = left_aabb.surface_area();
+
+                right_count += bins[nb_bins - 1 - i].count;
+                right_aabb.grow_aabb(&bins[nb_bins - 1 - i].aabb);
+                right_counts[nb_bins - 2 - i] = right_count;
+                right_areas[nb_bins - 2 - i] = right_aabb.surface_area();
+            }
+
+            for i in 0..nb_bins - 1 {
+                if left_counts[i] == 0 || right_counts[i] == 0 {
+                    continue;
+                }
+                let cost =
+                    left_counts[i] as f32 * left_areas[i] + right_counts[i] as f32 * right_areas[i];
+                let is_better = match best_split {
+                    Some(split) => cost < split.cost,
+                    None => true,
+                };
+                if is_better {
+                    best_split = Some(Split {
+                        axis,
+                        bin_index: i,
+                        bounds_min,
+                        scale,
+                        cost,
+                    });
+                }
+            }
+        }
+
+        best_split
+    }
+
+    fn subdivide(
+        &mut self,
+        node_index: usize,
+        depth: usize,
+        build_primitives: &[BuildPrimitive],
+        parameters: &BvhParameters,
+    ) {
+        let node = self.nodes[node_index];
+        let count = node.primitive_count as usize;
+        if count <= parameters.max_leaf_size || depth >= parameters.max_depth {
+            return;
+        }
+
+        let nb_bins = parameters.nb_bins.max(2);
+        let split = match self.find_best_split(&node, build_primitives, nb_bins) {
+            Some(split) => split,
+            // all the centroids are at the same position
+            None => return,
+        };
+
+        // splitting is not worth it compared to intersecting every primitive
+        let leaf_cost = node.get_aabb().surface_area() * count as f32;
+        if split.cost >= leaf_cost {
+            return;
+        }
+
+        // partition the primitives in place
+        let first = node.left_or_first as usize;
+        let mut i = first;
+        let mut j = first + count;
+        while i < j {
+            let primitive_index = self.primitive_indices[i] as usize;
+            if split.is_left(build_primitives[primitive_index].centroid, nb_bins) {
+                i += 1;
+            } else {
+                j -= 1;
+                self.primitive_indices.swap(i, j);
+            }
+        }
+
+        let left_count = i - first;
+        if left_count == 0 || left_count == count {
+            return;
+        }
+
+        // children are stored next to each other
+        let left_index = self.nodes.len();
+        self.nodes.push(BvhNodeGPU {
+            left_or_first: first as u32,
+            primitive_count: left_count as u32,
+            ..Default::default()
+        });
+        self.nodes.push(BvhNodeGPU {
+            left_or_first: i as u32,
+            primitive_count: (count - left_count) as u32,
+            ..Default::default()
+        });
+        self.nodes[node_index].left_or_first = left_index as u32;
+        self.nodes[node_index].primitive_count = 0;
+
+        self.update_node_bounds(left_index, build_primitives);
+        self.update_node_bounds(left_index + 1, build_primitives);
+        self.subdivide(left_index, depth + 1, build_primitives, parameters);
+        self.subdivide(left_index + 1, depth + 1, build_primitives, parameters);
+    }
+
+    /// Gets the depth of the deepest leaf
+    pub fn get_depth(&self) -> usize {
+        let mut max_depth = 0;
+        let mut stack = vec![(0, 0)];
+        while let Some((node_index, depth)) = stack.pop() {
+            let node = &self.nodes[node_index];
+            max_depth = max_depth.max(depth);
+            if !node.is_leaf() && node.left_or_first > 0 {
+                stack.push((node.left_or_first as usize, depth + 1));
+                stack.push((node.left_or_first as usize + 1, depth + 1));
+            }
+        }
+        max_depth
+    }
+
+    /// Checks that the node bounds contain their primitives and children
+    /// and that every primitive is referenced exactly once
+    pub fn check(&self, aabbs: &[Aabb]) {
+        let epsilon = 1e-5;
+        assert!(!self.nodes.is_empty());
+        assert!(self.primitive_indices.len() == aabbs.len());
+
+        let mut nb_references = vec![0; aabbs.len()];
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let node_aabb = node.get_aabb();
+
+            if node.is_leaf() {
+                let first = node.left_or_first as usize;
+                let count = node.primitive_count as usize;
+                assert!(first + count <= self.primitive_indices.len());
+                for &primitive_index in &self.primitive_indices[first..first + count] {
+                    let primitive_index = primitive_index as usize;
+                    nb_references[primitive_index] += 1;
+                    assert!(node_aabb.contains(&aabbs[primitive_index], epsilon));
+                }
+            } else if node.left_or_first > 0 {
+                let left_index = node.left_or_first as usize;
+                assert!(left_index > node_index && left_index + 1 < self.nodes.len());
+                for child_index in [left_index, left_index + 1] {
+                    assert!(node_aabb.contains(&self.nodes[child_index].get_aabb(), epsilon));
+                    stack.push(child_index);
+                }
+            } else {
+                // only an empty bvh has an internal root without children
+                assert!(node_index == 0 && aabbs.is_empty());
+            }
+        }
+
+        assert!(nb_references.iter().all(|&nb_reference| nb_reference == 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{Aabb, Bvh, BvhParameters};
+
+    /// Deterministic pseudo random numbers in [0, 1[, the tests do not depend on a seed
+    fn random_floats(count: usize) -> Vec<f32> {
+        let mut state: u32 = 0x2545_f491;
+        (0..count)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 8) as f32 / 16_777_216.
+            })
+            .collect()
+    }
+
+    fn random_points(count: usize, scale: f32) -> Vec<Vec3> {
+        random_floats(3 * count)
+            .chunks_exact(3)
+            .map(|xyz| scale * Vec3::from_slice(xyz))
+            .collect()
+    }
+
+    fn build_and_check(aabbs: &[Aabb], parameters: &BvhParameters) -> Bvh {
+        let bvh = Bvh::build(aabbs, parameters);
+        bvh.check(aabbs);
+        bvh
+    }
+
+    #[test]
+    fn random_boxes() {
+        let sizes = random_points(1000, 2.);
+        let aabbs: Vec<Aabb> = random_points(1000, 100.)
+            .into_iter()
+            .zip(sizes)
+            .map(|(min, size)| Aabb {
+                min,
+                max: min + size,
+            })
+            .collect();
+
+        let bvh = build_and_check(&aabbs, &BvhParameters::default());
+        assert!(bvh.nodes.len() > 1);
+
+        let parameters = BvhParameters {
+            max_leaf_size: 1,
+            max_depth: 64,
+            nb_bins: 4,
+        };
+        build_and_check(&aabbs, &parameters);
+    }
+
+    #[test]
+    fn depth_is_limited() {
+        let aabbs: Vec<Aabb> = random_points(1000, 10.)
+            .into_iter()
+            .map(|point| Aabb {
+                min: point,
+                max: point + 0.1,
+            })
+            .collect();
+        let parameters = BvhParameters {
+            max_leaf_size: 1,
+            max_depth: 3,
+            ..Default::default()
+        };
+
+        let bvh = build_and_check(&aabbs, &parameters);
+        assert!(bvh.get_depth() <= 3);
+    }
+
+    #[test]
+    fn coincident_boxes() {
+        let aabb = Aabb {
+            min: Vec3::new(-1., 2., 3.),
+            max: Vec3::new(1., 4., 5.),
+        };
+        let aabbs = vec![aabb; 100];
+
+        // the centroids cannot be separated, the root stays a leaf
+        let bvh = build_and_check(&aabbs, &BvhParameters::default());
+        assert_eq!(bvh.nodes.len(), 1);
+        assert_eq!(bvh.nodes[0].primitive_count, 100);
+    }
+
+    #[test]
+    fn degenerate_boxes() {
+        // points, then boxes flat along one axis like axis aligned triangles
+        let points: Vec<Aabb> = random_points(500, 50.)
+            .into_iter()
+            .map(|point| Aabb {
+                min: point,
+                max: point,
+            })
+            .collect();
+        build_and_check(&points, &BvhParameters::default());
+
+        let flat_boxes: Vec<Aabb> = random_points(500, 50.)
+            .into_iter()
+            .map(|point| Aabb {
+                min: Vec3::new(point.x, 0., point.z),
+                max: Vec3::new(point.x + 1., 0., point.z + 1.),
+            })
+            .collect();
+        build_and_check(&flat_boxes, &BvhParameters::default());
+    }
+
+    #[test]
+    fn empty_bvh() {
+        let bvh = build_and_check(&[], &BvhParameters::default());
+        assert_eq!(bvh.nodes.len(), 1);
+        assert!(bvh.nodes[0].get_aabb().is_empty());
+        assert_eq!(bvh.get_depth(), 0);
+    }
+
+    #[test]
+    fn single_primitive() {
+        let aabb = Aabb {
+            min: Vec3::ZERO,
+            max: Vec3::ONE,
+        };
+        let bvh = build_and_check(&[aabb], &BvhParameters::default());
+        assert_eq!(bvh.nodes.len(), 1);
+        assert!(bvh.nodes[0].is_leaf());
+        assert_eq!(bvh.primitive_indices, [0]);
+    }
+}
@@ -1,6 +1,13 @@
 use glam::{Mat4, Vec3, Vec4};
+use gpu_layout::GpuLayout;
 
-#[derive(Debug)]
+use super::scene_description::CameraDescription;
+
+/// Camera data as read by the shaders
+/// The layout matches the `Camera` struct in `raytracer/camera.slang`
+/// It is read as a uniform buffer, std140 places these fields like std430
+#[repr(C)]
+#[derive(Debug, Clone, Copy, GpuLayout)]
 pub struct CameraGPU {
     pub view_matrix: Mat4,
     pub projection_matrix: Mat4,
@@ -10,6 +17,7 @@ pub struct CameraGPU {
     pub plane_width: f32,
     pub plane_height: f32,
     pub plane_near: f32,
+    pub _pad0: u32,
 }
 
 pub enum CameraMovement {
@@ -91,6 +99,32 @@ impl Camera {
         camera
     }
 
+    /// Creates a camera with the pose and the projection of a scene file
+    pub fn from_description(description: &CameraDescription) -> Self {
+        let mut camera = Camera {
+            eye: Vec3::from_array(description.position),
+            fov: description.fov,
+            near: description.near,
+            far: description.far,
+            yaw: description.yaw,
+            pitch: description.pitch,
+            ..Default::default()
+        };
+        camera.update_vectors();
+        camera
+    }
+
+    pub fn get_description(&self) -> CameraDescription {
+        CameraDescription {
+            position: self.eye.to_array(),
+            yaw: self.yaw,
+            pitch: self.pitch,
+            fov: self.fov,
+            near: self.near,
+            far: self.far,
+        }
+    }
+
     fn update_vectors(&mut self) {
         // calculate the new at vector
         let mut front = Vec3::ZERO;
@@ -168,6 +202,7 @@ impl Camera {
             plane_width,
             plane_height,
             plane_near: self.near,
+            _pad0: 0,
         }
     }
 }
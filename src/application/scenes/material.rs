@@ -1,14 +1,121 @@
-use glam::Vec4;
+use glam::{Vec3, Vec4};
+use gpu_layout::GpuLayout;
 
-#[derive(Clone, Copy)]
+use super::texture::{ColorSpace, NO_TEXTURE};
+
+/// A material, uploaded as is to the shaders
+/// The layout matches the `Material` struct in `scene/material.slang`
+/// The factors are multiplied by the textures, the texture indices are `NO_TEXTURE` without them
+#[repr(C)]
+#[derive(Clone, Copy, GpuLayout)]
 pub struct Material {
     pub albedo: Vec4,
+    pub emissive: Vec3,
+    pub roughness: f32,
+    pub metalness: f32,
+    pub albedo_texture: u32,
+    /// Tangent space normals, computed from the texture coordinates of the triangles
+    pub normal_texture: u32,
+    /// Roughness in the green channel and metalness in the blue one, like glTF
+    pub roughness_metalness_texture: u32,
+    pub emissive_texture: u32,
+    pub _pad0: u32,
+    pub _pad1: u32,
+    pub _pad2: u32,
 }
 
 impl Default for Material {
     fn default() -> Self {
         Self {
             albedo: Vec4::from_array([1., 1., 1., 1.]),
+            emissive: Vec3::ZERO,
+            roughness: 1.,
+            metalness: 0.,
+            albedo_texture: NO_TEXTURE,
+            normal_texture: NO_TEXTURE,
+            roughness_metalness_texture: NO_TEXTURE,
+            emissive_texture: NO_TEXTURE,
+            _pad0: 0,
+            _pad1: 0,
+            _pad2: 0,
+        }
+    }
+}
+
+impl Material {
+    /// Creates a material from a MTL entry using its diffuse colour and its textures
+    /// `get_texture` gives the index of the texture of a file of the MTL
+    pub fn from_tobj(
+        material: &tobj::Material,
+        mut get_texture: impl FnMut(&str, ColorSpace) -> u32,
+    ) -> Self {
+        let default_material = Material::default();
+        let albedo = match material.diffuse {
+            Some([r, g, b]) => Vec4::new(r, g, b, material.dissolve.unwrap_or(1.)),
+            None => default_material.albedo,
+        };
+        // the emission is not part of the MTL files tobj knows
+        let emissive = material
+            .unknown_param
+            .get("Ke")
+            .and_then(|value| {
+                let channels: Vec<f32> = value
+                    .split_whitespace()
+                    .filter_map(|channel| channel.parse().ok())
+                    .collect();
+                (channels.len() == 3).then(|| Vec3::from_slice(&channels))
+            })
+            .unwrap_or(default_material.emissive);
+
+        let mut get_optional_texture = |file: Option<&String>, color_space| {
+            file.map_or(NO_TEXTURE, |file| get_texture(file, color_space))
+        };
+        Self {
+            albedo,
+            emissive,
+            albedo_texture: get_optional_texture(
+                material.diffuse_texture.as_ref(),
+                ColorSpace::Srgb,
+            ),
+            normal_texture: get_optional_texture(
+                material.normal_texture.as_ref(),
+                ColorSpace::Linear,
+            ),
+            emissive_texture: get_optional_texture(
+                material.unknown_param.get("map_Ke"),
+                ColorSpace::Srgb,
+            ),
+            ..default_material
+        }
+    }
+
+    /// Creates a material from a glTF pbrMetallicRoughness material and its textures
+    /// `get_texture` gives the index of the texture of a glTF texture
+    pub fn from_gltf(
+        material: &gltf::Material,
+        mut get_texture: impl FnMut(&gltf::Texture, ColorSpace) -> u32,
+    ) -> Self {
+        let pbr = material.pbr_metallic_roughness();
+        Self {
+            albedo: Vec4::from_array(pbr.base_color_factor()),
+            emissive: Vec3::from_array(material.emissive_factor()),
+            roughness: pbr.roughness_factor(),
+            metalness: pbr.metallic_factor(),
+            albedo_texture: pbr.base_color_texture().map_or(NO_TEXTURE, |info| {
+                get_texture(&info.texture(), ColorSpace::Srgb)
+            }),
+            normal_texture: material.normal_texture().map_or(NO_TEXTURE, |normal| {
+                get_texture(&normal.texture(), ColorSpace::Linear)
+            }),
+            roughness_metalness_texture: pbr
+                .metallic_roughness_texture()
+                .map_or(NO_TEXTURE, |info| {
+                    get_texture(&info.texture(), ColorSpace::Linear)
+                }),
+            emissive_texture: material.emissive_texture().map_or(NO_TEXTURE, |info| {
+                get_texture(&info.texture(), ColorSpace::Srgb)
+            }),
+            ..Default::default()
         }
     }
 }
@@ -0,0 +1,28 @@
+use gpu_layout::GpuLayout;
+
+/// Range of the scene triangles shared by every model using this mesh
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Mesh {
+    pub first_triangle: usize,
+    pub nb_triangles: usize,
+}
+
+/// Mesh data as read by the shaders
+/// The layout matches the `Mesh` struct in `scene/mesh.slang`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, GpuLayout)]
+pub struct MeshGPU {
+    pub first_triangle: u32,
+    pub nb_triangles: u32,
+    pub blas_root: u32,
+}
+
+impl Mesh {
+    pub fn get_gpu_data(&self, blas_root: u32) -> MeshGPU {
+        MeshGPU {
+            first_triangle: self.first_triangle as u32,
+            nb_triangles: self.nb_triangles as u32,
+            blas_root,
+        }
+    }
+}
@@ -1,5 +1,10 @@
+pub mod acceleration_structure;
+pub mod bvh;
 pub mod camera;
 pub mod material;
+pub mod mesh;
 pub mod model;
 pub mod scene;
+pub mod scene_description;
+pub mod texture;
 pub mod triangle;
@@ -1,11 +1,27 @@
-use glam::Mat4;
+use glam::{Mat4, Vec2, Vec4};
+use gpu_layout::GpuLayout;
 
 use super::triangle::Triangle;
 
+/// An instance of a mesh in the scene
 #[derive(Clone, Copy, Debug)]
 pub struct Model {
     pub model_matrix: Mat4,
     pub material_index: usize,
+    pub mesh_index: usize,
+}
+
+/// Model data as read by the shaders
+/// The layout matches the `Model` struct in `scene/model.slang`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, GpuLayout)]
+pub struct ModelGPU {
+    pub model_matrix: Mat4,
+    pub model_matrix_inverse: Mat4,
+    pub material_index: u32,
+    pub mesh_index: u32,
+    pub _pad0: u32,
+    pub _pad1: u32,
 }
 
 impl Default for Model {
@@ -13,6 +29,7 @@ impl Default for Model {
         Self {
             model_matrix: Mat4::IDENTITY,
             material_index: 0,
+            mesh_index: 0,
         }
     }
 }
@@ -22,8 +39,115 @@ impl Model {
         (Model::default(), vec![Triangle::default()])
     }
 
-    pub fn from_tobj(model: &tobj::Model) -> (Self, Vec<Triangle>) {
-        // TODO:
-        todo!("{:?}", model)
+    pub fn get_gpu_data(&self) -> ModelGPU {
+        ModelGPU {
+            model_matrix: self.model_matrix,
+            model_matrix_inverse: self.model_matrix.inverse(),
+            material_index: self.material_index as u32,
+            mesh_index: self.mesh_index as u32,
+            _pad0: 0,
+            _pad1: 0,
+        }
+    }
+
+    /// Converts a triangulated tobj mesh into a model and its triangles
+    /// The triangles are tagged with the given mesh index
+    pub fn from_tobj(
+        model: &tobj::Model,
+        mesh_index: usize,
+        material_index: usize,
+    ) -> (Self, Vec<Triangle>) {
+        let mesh = &model.mesh;
+        let get_position = |index: u32| {
+            let index = 3 * index as usize;
+            Vec4::new(
+                mesh.positions[index],
+                mesh.positions[index + 1],
+                mesh.positions[index + 2],
+                1.,
+            )
+        };
+        // the OBJ texture coordinates start at the bottom of the textures
+        let get_uv = |index: u32| {
+            let index = 2 * index as usize;
+            match mesh.texcoords.get(index..index + 2) {
+                Some(&[u, v]) => Vec2::new(u, 1. - v),
+                _ => Vec2::ZERO,
+            }
+        };
+
+        // the mesh is loaded with `triangulate` set so every face has three indices
+        let triangles = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|face| Triangle {
+                p0: get_position(face[0]),
+                p1: get_position(face[1]),
+                p2: get_position(face[2]),
+                uv0: get_uv(face[0]),
+                uv1: get_uv(face[1]),
+                uv2: get_uv(face[2]),
+                mesh_index: mesh_index as u32,
+                _pad0: 0,
+            })
+            .collect();
+
+        let new_model = Model {
+            model_matrix: Mat4::IDENTITY,
+            material_index,
+            mesh_index,
+        };
+
+        (new_model, triangles)
+    }
+
+    /// Converts a glTF triangle primitive into a model and its triangles
+    /// The node transform is baked into the model matrix
+    pub fn from_gltf(
+        primitive: &gltf::Primitive,
+        buffers: &[gltf::buffer::Data],
+        model_matrix: Mat4,
+        mesh_index: usize,
+        material_index: usize,
+    ) -> (Self, Vec<Triangle>) {
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+        let positions: Vec<Vec4> = reader
+            .read_positions()
+            .map(|positions| positions.map(|[x, y, z]| Vec4::new(x, y, z, 1.)).collect())
+            .unwrap_or_default();
+        // the primitives without texture coordinates only use the factors of their material
+        let uvs: Vec<Vec2> = reader
+            .read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().map(Vec2::from_array).collect())
+            .unwrap_or_else(|| vec![Vec2::ZERO; positions.len()]);
+
+        // non indexed primitives use consecutive vertices
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+
+        let triangles = indices
+            .chunks_exact(3)
+            .map(|face| Triangle {
+                p0: positions[face[0] as usize],
+                p1: positions[face[1] as usize],
+                p2: positions[face[2] as usize],
+                uv0: uvs[face[0] as usize],
+                uv1: uvs[face[1] as usize],
+                uv2: uvs[face[2] as usize],
+                mesh_index: mesh_index as u32,
+                _pad0: 0,
+            })
+            .collect();
+
+        let new_model = Model {
+            model_matrix,
+            material_index,
+            mesh_index,
+        };
+
+        (new_model, triangles)
     }
 }
@@ -1,130 +1,670 @@
-use crate::application::{vk_app::VulkanApp, vulkan::vk_buffer::BufferGPU};
+use ash::{
+    vk::{BufferUsageFlags, CommandBuffer, Sampler},
+    Device,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
-use super::{camera::Camera, material::Material, model::Model, triangle::Triangle};
+use glam::{Mat4, Vec3, Vec4};
+use gpu_layout::GpuLayout;
+use log::{debug, warn};
+
+use crate::application::{
+    error::{Error, Result},
+    pipelines::vk_descriptors::MAX_VARIABLE_DESCRIPTOR_COUNT,
+    vk_app::{AllocatedImage, VulkanApp},
+    vulkan::vk_buffer::BufferGPU,
+};
+
+use super::{
+    acceleration_structure::{BottomLevelAccelerationStructure, TopLevelAccelerationStructure},
+    bvh::BvhParameters,
+    camera::Camera,
+    material::Material,
+    mesh::{Mesh, MeshGPU},
+    model::{Model, ModelGPU},
+    scene_description::SceneDescription,
+    texture::{ColorSpace, Texture, NO_TEXTURE},
+    triangle::Triangle,
+};
 
 #[derive(Default)]
 pub struct Scene {
     pub triangles: Vec<Triangle>,
+    pub meshes: Vec<Mesh>,
     pub models: Vec<Model>,
     pub materials: Vec<Material>,
+    /// Textures of the materials, the shaders read them from a single array of descriptors
+    pub textures: Vec<Texture>,
+    /// Index in `textures` of each loaded file, the files shared by several materials are loaded once
+    texture_indices: HashMap<(PathBuf, ColorSpace), u32>,
     pub buffers: Option<SceneBuffers>,
 
+    pub bvh_parameters: BvhParameters,
+    /// Rebuilt only when meshes are added
+    pub blas: Option<BottomLevelAccelerationStructure>,
+    /// Rebuilt when models are added or moved
+    pub tlas: Option<TopLevelAccelerationStructure>,
+
     pub camera: Option<Camera>,
+
+    /// Description the scene was loaded from, updated when the scene is saved
+    pub description: SceneDescription,
+    /// Index in `materials` of each material of the description
+    pub described_material_indices: Vec<usize>,
+
+    /// Set when the content of the scene changes, the renderer resets its accumulation
+    pub is_modified: bool,
+}
+
+/// Number of elements in the scene buffers, sent to the shaders as push constants
+/// The layout matches the `SceneCounts` struct in `scene/counts.slang`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, GpuLayout)]
+pub struct SceneCountsGPU {
+    pub nb_triangles: u32,
+    pub nb_models: u32,
+    pub nb_materials: u32,
+    pub nb_lights: u32,
 }
 
 pub struct SceneBuffers {
     pub triangles_buffer: BufferGPU,
     pub models_buffer: BufferGPU,
     pub materials_buffer: BufferGPU,
+    pub camera_buffer: BufferGPU,
+    pub meshes_buffer: BufferGPU,
+    pub tlas_nodes_buffer: BufferGPU,
+    pub tlas_model_indices_buffer: BufferGPU,
+    pub blas_nodes_buffer: BufferGPU,
+    pub blas_triangle_indices_buffer: BufferGPU,
+    /// Same order as `Scene::textures`
+    pub textures: Vec<AllocatedImage>,
+    pub texture_sampler: Sampler,
+    pub counts: SceneCountsGPU,
 }
 
 impl Scene {
-    pub fn init(&mut self) {
+    /// Loads the scene file, a `.ron` description or a single OBJ or glTF file
+    /// Without it, the scene is a single triangle
+    pub fn init(&mut self, scene_file: Option<&Path>) -> Result<()> {
         // init the scene materials
         self.add_material(Material::default());
 
         // init the scene models
-        // let teapot = String::from("/src/assets/models/teapot.obj");
-        // self.add_model(teapot);
-        let (model, triangles) = Model::triangle();
-        self.models.push(model);
-        self.triangles = triangles;
+        let description = match scene_file {
+            Some(scene_file) if scene_file.extension().is_some_and(|ext| ext == "ron") => {
+                SceneDescription::load(scene_file)?
+            }
+            Some(scene_file) => SceneDescription::from_mesh_file(scene_file),
+            None => {
+                // let teapot = concat!(env!("CARGO_MANIFEST_DIR"), "/src/assets/models/teapot.obj");
+                // self.add_model(Path::new(teapot))?;
+                let (model, triangles) = Model::triangle();
+                self.add_mesh(triangles);
+                self.models.push(model);
+                SceneDescription::default()
+            }
+        };
+
+        // init the camera and the described meshes
+        self.add_description(description)
+    }
+
+    /// Adds the materials and the meshes of a description and uses its camera
+    pub fn add_description(&mut self, description: SceneDescription) -> Result<()> {
+        let mut material_indices = Vec::with_capacity(description.materials.len());
+        for material in description.materials.iter() {
+            let mut get_texture = |path: &Option<PathBuf>, color_space| match path {
+                Some(path) => self.add_texture(path, color_space),
+                None => Ok(NO_TEXTURE),
+            };
+            let material = Material {
+                albedo: Vec4::from_array(material.albedo),
+                emissive: Vec3::from_array(material.emissive),
+                roughness: material.roughness,
+                metalness: material.metalness,
+                albedo_texture: get_texture(&material.albedo_texture, ColorSpace::Srgb)?,
+                normal_texture: get_texture(&material.normal_texture, ColorSpace::Linear)?,
+                roughness_metalness_texture: get_texture(
+                    &material.roughness_metalness_texture,
+                    ColorSpace::Linear,
+                )?,
+                emissive_texture: get_texture(&material.emissive_texture, ColorSpace::Srgb)?,
+                ..Default::default()
+            };
+            self.add_material(material);
+            material_indices.push(self.materials.len() - 1);
+        }
+
+        for mesh in description.meshes.iter() {
+            let material_index = match &mesh.material {
+                Some(name) => {
+                    let position = description
+                        .materials
+                        .iter()
+                        .position(|material| &material.name == name)
+                        .ok_or_else(|| Error::SceneLoad {
+                            path: mesh.path.to_string_lossy().into_owned(),
+                            message: format!("the material `{}' is not described", name),
+                        })?;
+                    Some(material_indices[position])
+                }
+                None => None,
+            };
+
+            // the transform is applied on top of the transforms of the file
+            let first_model = self.models.len();
+            self.add_file(&mesh.path)?;
+            let transform = mesh.transform.get_matrix();
+            for model in self.models[first_model..].iter_mut() {
+                model.model_matrix = transform * model.model_matrix;
+                if let Some(material_index) = material_index {
+                    model.material_index = material_index;
+                }
+            }
+        }
+
+        if !description.lights.is_empty() || description.environment_map.is_some() {
+            warn!("The lights and the environment map are saved but not rendered yet");
+        }
+
+        self.camera = Some(Camera::from_description(&description.camera));
+        self.described_material_indices
+            .extend_from_slice(&material_indices);
+        self.description.materials.extend(description.materials);
+        self.description.meshes.extend(description.meshes);
+        self.description.lights.extend(description.lights);
+        self.description.camera = description.camera;
+        if description.environment_map.is_some() {
+            self.description.environment_map = description.environment_map;
+        }
+        self.is_modified = true;
+        Ok(())
+    }
+
+    /// Writes the scene as a `.ron` description with the current camera and materials
+    pub fn save_description(&self, path: &Path) -> Result<()> {
+        let mut description = self.description.clone();
+        if let Some(camera) = &self.camera {
+            description.camera = camera.get_description();
+        }
+        for (material, &index) in description
+            .materials
+            .iter_mut()
+            .zip(self.described_material_indices.iter())
+        {
+            let scene_material = &self.materials[index];
+            material.albedo = scene_material.albedo.to_array();
+            material.emissive = scene_material.emissive.to_array();
+            material.roughness = scene_material.roughness;
+            material.metalness = scene_material.metalness;
+        }
+        description.save(path)
+    }
 
-        // init the camera
-        self.camera = Some(Camera::default());
+    /// Adds the content of an OBJ or glTF file, chosen by its extension
+    pub fn add_file(&mut self, path: &Path) -> Result<()> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("obj") => self.add_model(path),
+            Some("gltf" | "glb") => self.add_gltf(path),
+            _ => Err(Error::SceneLoad {
+                path: path.to_string_lossy().into_owned(),
+                message: "unsupported extension, expected .obj, .gltf or .glb".to_string(),
+            }),
+        }
     }
 
-    pub fn clear(&mut self, allocator: &vk_mem::Allocator) {
+    pub fn clear(&mut self, device: &Device, allocator: &vk_mem::Allocator) {
         let buffers = match self.buffers {
             None => return,
             Some(ref mut buffers) => buffers,
         };
+        for texture in buffers.textures.iter_mut() {
+            VulkanApp::clear_image(device, allocator, texture);
+        }
+        unsafe { device.destroy_sampler(buffers.texture_sampler, None) };
         VulkanApp::clear_buffer(allocator, &mut buffers.triangles_buffer.buffer);
         VulkanApp::clear_buffer(allocator, &mut buffers.models_buffer.buffer);
         VulkanApp::clear_buffer(allocator, &mut buffers.materials_buffer.buffer);
+        VulkanApp::clear_buffer(allocator, &mut buffers.camera_buffer.buffer);
+        VulkanApp::clear_buffer(allocator, &mut buffers.meshes_buffer.buffer);
+        VulkanApp::clear_buffer(allocator, &mut buffers.tlas_nodes_buffer.buffer);
+        VulkanApp::clear_buffer(allocator, &mut buffers.tlas_model_indices_buffer.buffer);
+        VulkanApp::clear_buffer(allocator, &mut buffers.blas_nodes_buffer.buffer);
+        VulkanApp::clear_buffer(allocator, &mut buffers.blas_triangle_indices_buffer.buffer);
+    }
+
+    /// Adds the triangles of a new mesh, they must be tagged with the new mesh index
+    pub fn add_mesh(&mut self, triangles: Vec<Triangle>) -> usize {
+        let mesh_index = self.meshes.len();
+        debug_assert!(triangles
+            .iter()
+            .all(|triangle| triangle.mesh_index as usize == mesh_index));
+
+        self.meshes.push(Mesh {
+            first_triangle: self.triangles.len(),
+            nb_triangles: triangles.len(),
+        });
+        self.triangles.extend(triangles);
+
+        self.blas = None;
+        self.tlas = None;
+        self.is_modified = true;
+        mesh_index
+    }
+
+    /// Adds a new instance of an existing model sharing its mesh
+    /// Returns the index of the new model
+    pub fn add_existing_model(&mut self, model_id: usize) -> usize {
+        let model = self.models[model_id];
+        self.models.push(model);
+        self.tlas = None;
+        self.is_modified = true;
+        self.models.len() - 1
     }
 
-    pub fn add_existing_model(&mut self, model_id: usize) {
-        // TODO:
-        todo!("need to implement {}", model_id)
+    /// Moves a model, only the top level acceleration structure needs to be rebuilt
+    pub fn set_model_matrix(&mut self, model_id: usize, model_matrix: Mat4) {
+        self.models[model_id].model_matrix = model_matrix;
+        self.tlas = None;
+        self.is_modified = true;
+    }
+
+    /// Builds the acceleration structures that are out of date
+    pub fn build_acceleration_structures(&mut self) {
+        if self.blas.is_none() {
+            let blas = BottomLevelAccelerationStructure::build(
+                &self.triangles,
+                &self.meshes,
+                &self.bvh_parameters,
+            );
+            debug!(
+                "Built the bottom level acceleration structure of {} meshes with {} nodes",
+                self.meshes.len(),
+                blas.nodes.len()
+            );
+            self.blas = Some(blas);
+        }
+
+        if self.tlas.is_none() {
+            let tlas = TopLevelAccelerationStructure::build(
+                &self.models,
+                self.blas.as_ref().unwrap(),
+                &self.bvh_parameters,
+            );
+            debug!(
+                "Built the top level acceleration structure of {} models with a depth of {}",
+                self.models.len(),
+                tlas.bvh.get_depth()
+            );
+            self.tlas = Some(tlas);
+        }
     }
 
     pub fn add_material(&mut self, material: Material) {
         self.materials.push(material);
+        self.is_modified = true;
+    }
+
+    /// Loads a PNG, JPEG or HDR file once for each color space
+    /// Returns the index of the texture the materials reference
+    pub fn add_texture(&mut self, path: &Path, color_space: ColorSpace) -> Result<u32> {
+        let key = (path.to_path_buf(), color_space);
+        if let Some(&texture_index) = self.texture_indices.get(&key) {
+            return Ok(texture_index);
+        }
+        let texture_index = self.push_texture(Texture::load(path, color_space)?)?;
+        self.texture_indices.insert(key, texture_index);
+        Ok(texture_index)
     }
 
-    pub fn add_model(&mut self, obj_path: String) {
-        // TODO: fix this
+    /// The textures of the mesh files are optional, without them the materials only use their factors
+    fn get_texture_or_warn(texture_index: Result<u32>) -> u32 {
+        match texture_index {
+            Ok(texture_index) => texture_index,
+            Err(err) => {
+                warn!("{}, the material is used without this texture", err);
+                NO_TEXTURE
+            }
+        }
+    }
+
+    fn push_texture(&mut self, texture: Texture) -> Result<u32> {
+        if self.textures.len() >= MAX_VARIABLE_DESCRIPTOR_COUNT as usize {
+            return Err(Error::SceneLoad {
+                path: texture.name,
+                message: format!(
+                    "the scene has more than {} textures",
+                    MAX_VARIABLE_DESCRIPTOR_COUNT
+                ),
+            });
+        }
+        debug!(
+            "Loaded texture `{}' of {}x{} texels",
+            texture.name, texture.width, texture.height
+        );
+        self.textures.push(texture);
+        self.is_modified = true;
+        Ok(self.textures.len() as u32 - 1)
+    }
+
+    pub fn add_model(&mut self, obj_path: &Path) -> Result<()> {
         // read obj file
-        let crate_path = env!("CARGO_MANIFEST_DIR");
-        let model_path = crate_path.to_owned() + &obj_path;
+        let model_path = obj_path.to_string_lossy().into_owned();
 
         // create a model
-        let loaded_model = tobj::load_obj(model_path.clone(), &tobj::GPU_LOAD_OPTIONS);
-        assert!(loaded_model.is_ok());
+        let (models, materials) =
+            tobj::load_obj(obj_path, &tobj::GPU_LOAD_OPTIONS).map_err(|err| Error::SceneLoad {
+                path: model_path.clone(),
+                message: err.to_string(),
+            })?;
 
-        let (models, materials) = loaded_model.unwrap_or_else(|err| {
-            panic!("Failed to load OBJ file {}: {:?}", model_path.clone(), err)
-        });
-
-        // Materials might report a separate loading error if the MTL file wasn't found.
-        // If you don't need the materials, you can generate a default here and use that
-        // instead.
+        // Materials might report a separate loading error if the MTL file wasn't found
         let materials = materials.unwrap_or_else(|err| {
-            panic!(
-                "Failed to load MTL file for {}: {:?}",
-                model_path.clone(),
-                err
-            )
+            warn!(
+                "Failed to load MTL file for {}: {:?}, using a default material instead",
+                model_path, err
+            );
+            Vec::new()
         });
 
-        for (i, m) in models.iter().enumerate() {
-            let mesh = &m.mesh;
+        // the MTL materials are appended after the existing scene materials
+        // the texture paths are relative to the OBJ file
+        let material_offset = self.materials.len();
+        let directory = obj_path.parent().unwrap_or(Path::new(""));
+        for material in materials.iter() {
+            let material = Material::from_tobj(material, |file, color_space| {
+                Self::get_texture_or_warn(self.add_texture(&directory.join(file), color_space))
+            });
+            self.add_material(material);
+        }
+
+        // meshes without a valid material use a default one
+        let mut default_material_index = None;
 
-            println!("model[{}].name = \'{}\'", i, m.name);
-            println!("model[{}].mesh.material_id = {:?}", i, mesh.material_id);
+        for m in models.iter() {
+            let material_index = match m.mesh.material_id {
+                Some(material_id) if material_id < materials.len() => material_offset + material_id,
+                _ => *default_material_index.get_or_insert_with(|| {
+                    self.add_material(Material::default());
+                    self.materials.len() - 1
+                }),
+            };
 
-            println!(
-                "Size of model[{}].face_arities: {}",
-                i,
-                mesh.face_arities.len()
+            let mesh_index = self.meshes.len();
+            let (model, triangles) = Model::from_tobj(m, mesh_index, material_index);
+            debug!(
+                "Loaded model `{}' with {} triangles",
+                m.name,
+                triangles.len()
             );
+            self.add_mesh(triangles);
+            self.models.push(model);
+        }
+        Ok(())
+    }
+
+    pub fn add_gltf(&mut self, gltf_path: &Path) -> Result<()> {
+        // read gltf or glb file
+        let scene_path = gltf_path.to_string_lossy().into_owned();
+
+        let (document, buffers, images) =
+            gltf::import(gltf_path).map_err(|err| Error::SceneLoad {
+                path: scene_path.clone(),
+                message: err.to_string(),
+            })?;
+
+        // the glTF materials are appended after the existing scene materials
+        // an image used as a color and as data is uploaded twice
+        let material_offset = self.materials.len();
+        let mut image_textures: HashMap<(usize, ColorSpace), u32> = HashMap::new();
+        for material in document.materials() {
+            let material = Material::from_gltf(&material, |texture, color_space| {
+                let image_index = texture.source().index();
+                *image_textures
+                    .entry((image_index, color_space))
+                    .or_insert_with(|| {
+                        let name = format!("{} image {}", scene_path, image_index);
+                        let texture = Texture::from_gltf(&images[image_index], name, color_space);
+                        Self::get_texture_or_warn(
+                            texture.and_then(|texture| self.push_texture(texture)),
+                        )
+                    })
+            });
+            self.add_material(material);
+        }
+
+        // primitives without a material use a default one
+        let mut default_material_index = None;
+        // glTF meshes referenced by several nodes are instanced
+        let mut primitive_meshes = HashMap::new();
+
+        let gltf_scene = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(gltf_scene) => gltf_scene,
+            None => {
+                warn!("The glTF file {} does not contain any scene", scene_path);
+                return Ok(());
+            }
+        };
+
+        for node in gltf_scene.nodes() {
+            self.add_gltf_node(
+                &node,
+                &buffers,
+                Mat4::IDENTITY,
+                material_offset,
+                &mut default_material_index,
+                &mut primitive_meshes,
+            );
+        }
+        Ok(())
+    }
+
+    fn add_gltf_node(
+        &mut self,
+        node: &gltf::Node,
+        buffers: &[gltf::buffer::Data],
+        parent_matrix: Mat4,
+        material_offset: usize,
+        default_material_index: &mut Option<usize>,
+        primitive_meshes: &mut HashMap<(usize, usize), usize>,
+    ) {
+        let model_matrix = parent_matrix * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    warn!(
+                        "Skipping primitive of mesh `{}' with unsupported mode {:?}",
+                        mesh.name().unwrap_or_default(),
+                        primitive.mode()
+                    );
+                    continue;
+                }
+
+                let material_index = match primitive.material().index() {
+                    Some(material_id) => material_offset + material_id,
+                    None => *default_material_index.get_or_insert_with(|| {
+                        self.add_material(Material::default());
+                        self.materials.len() - 1
+                    }),
+                };
 
-            let mut next_face = 0;
-            for f in 0..mesh.face_arities.len() {
-                let end = next_face + mesh.face_arities[f] as usize;
-                let face_indices: Vec<_> = mesh.indices[next_face..end].iter().collect();
-                println!("    face[{}] = {:?}", f, face_indices);
-                next_face = end;
+                // the primitive was already loaded by another node
+                if let Some(&mesh_index) = primitive_meshes.get(&(mesh.index(), primitive.index()))
+                {
+                    self.models.push(Model {
+                        model_matrix,
+                        material_index,
+                        mesh_index,
+                    });
+                    continue;
+                }
+
+                let mesh_index = self.meshes.len();
+                let (model, triangles) = Model::from_gltf(
+                    &primitive,
+                    buffers,
+                    model_matrix,
+                    mesh_index,
+                    material_index,
+                );
+                debug!(
+                    "Loaded primitive of mesh `{}' with {} triangles",
+                    mesh.name().unwrap_or_default(),
+                    triangles.len()
+                );
+                self.add_mesh(triangles);
+                self.models.push(model);
+                primitive_meshes.insert((mesh.index(), primitive.index()), mesh_index);
             }
-            // Normals and texture coordinates are also loaded, but not printed in this example
-            println!("model[{}].vertices: {}", i, mesh.positions.len() / 3);
-            assert!(mesh.positions.len() % 3 == 0);
-            for _ in 0..mesh.positions.len() / 3 {}
         }
 
-        for (i, m) in materials.iter().enumerate() {
-            println!("material[{}].name = \'{}\'", i, m.name);
+        for child in node.children() {
+            self.add_gltf_node(
+                &child,
+                buffers,
+                model_matrix,
+                material_offset,
+                default_material_index,
+                primitive_meshes,
+            );
         }
     }
 
-    fn upload_triangles(&self, application: &VulkanApp) -> BufferGPU {
+    fn upload_triangles(&self, application: &VulkanApp) -> Result<BufferGPU> {
         BufferGPU::upload_elements(&self.triangles, application)
     }
 
-    fn upload_models(&self, application: &VulkanApp) -> BufferGPU {
-        BufferGPU::upload_elements(&self.models, application)
+    fn upload_models(&self, application: &VulkanApp) -> Result<BufferGPU> {
+        let models: Vec<ModelGPU> = self.models.iter().map(Model::get_gpu_data).collect();
+        BufferGPU::upload_elements(&models, application)
     }
 
-    fn upload_materials(&self, application: &VulkanApp) -> BufferGPU {
+    fn upload_meshes(
+        &self,
+        blas: &BottomLevelAccelerationStructure,
+        application: &VulkanApp,
+    ) -> Result<BufferGPU> {
+        let meshes: Vec<MeshGPU> = self
+            .meshes
+            .iter()
+            .zip(blas.roots.iter())
+            .map(|(mesh, &blas_root)| mesh.get_gpu_data(blas_root))
+            .collect();
+        BufferGPU::upload_elements(&meshes, application)
+    }
+
+    fn upload_materials(&self, application: &VulkanApp) -> Result<BufferGPU> {
         BufferGPU::upload_elements(&self.materials, application)
     }
 
-    pub fn upload_buffers(&self, application: &VulkanApp) -> SceneBuffers {
-        SceneBuffers {
-            triangles_buffer: self.upload_triangles(application),
-            models_buffer: self.upload_models(application),
-            materials_buffer: self.upload_materials(application),
+    fn upload_camera(&self, application: &VulkanApp) -> Result<BufferGPU> {
+        let camera = [self.camera.as_ref().unwrap().get_gpu_data()];
+        BufferGPU::upload_elements_with_usage(
+            &camera,
+            BufferUsageFlags::UNIFORM_BUFFER,
+            application,
+        )
+    }
+
+    fn upload_textures(&self, application: &VulkanApp) -> Result<Vec<AllocatedImage>> {
+        self.textures
+            .iter()
+            .map(|texture| application.upload_texture(texture))
+            .collect()
+    }
+
+    pub fn get_counts(&self) -> SceneCountsGPU {
+        SceneCountsGPU {
+            nb_triangles: self.triangles.len() as u32,
+            nb_models: self.models.len() as u32,
+            nb_materials: self.materials.len() as u32,
+            // the lights of the descriptions are stored but not rendered yet
+            nb_lights: 0,
+        }
+    }
+
+    /// Uploads the scene, the acceleration structures must have been built
+    pub fn upload_buffers(&self, application: &VulkanApp) -> Result<SceneBuffers> {
+        let (blas, tlas) = match (&self.blas, &self.tlas) {
+            (Some(blas), Some(tlas)) => (blas, tlas),
+            _ => {
+                return Err(Error::SceneUpload(String::from(
+                    "the acceleration structures are not built",
+                )))
+            }
+        };
+
+        Ok(SceneBuffers {
+            triangles_buffer: self.upload_triangles(application)?,
+            models_buffer: self.upload_models(application)?,
+            materials_buffer: self.upload_materials(application)?,
+            camera_buffer: self.upload_camera(application)?,
+            meshes_buffer: self.upload_meshes(blas, application)?,
+            tlas_nodes_buffer: BufferGPU::upload_elements(&tlas.bvh.nodes, application)?,
+            tlas_model_indices_buffer: BufferGPU::upload_elements(
+                &tlas.bvh.primitive_indices,
+                application,
+            )?,
+            blas_nodes_buffer: BufferGPU::upload_elements(&blas.nodes, application)?,
+            blas_triangle_indices_buffer: BufferGPU::upload_elements(
+                &blas.triangle_indices,
+                application,
+            )?,
+            textures: self.upload_textures(application)?,
+            texture_sampler: VulkanApp::create_texture_sampler(&application.device)?,
+            counts: self.get_counts(),
+        })
+    }
+
+    /// Uploads the models and the top level acceleration structure again, after models were added or moved
+    /// The meshes and their bottom level acceleration structure are unchanged
+    /// The previous buffers are destroyed, the gpu must not be using them anymore
+    pub fn upload_model_buffers(
+        &self,
+        buffers: &mut SceneBuffers,
+        application: &VulkanApp,
+    ) -> Result<()> {
+        let tlas = self.tlas.as_ref().ok_or_else(|| {
+            Error::SceneUpload(String::from(
+                "the top level acceleration structure is not built",
+            ))
+        })?;
+
+        let mut models_buffer = self.upload_models(application)?;
+        let mut tlas_nodes_buffer = BufferGPU::upload_elements(&tlas.bvh.nodes, application)?;
+        let mut tlas_model_indices_buffer =
+            BufferGPU::upload_elements(&tlas.bvh.primitive_indices, application)?;
+
+        std::mem::swap(&mut buffers.models_buffer, &mut models_buffer);
+        std::mem::swap(&mut buffers.tlas_nodes_buffer, &mut tlas_nodes_buffer);
+        std::mem::swap(
+            &mut buffers.tlas_model_indices_buffer,
+            &mut tlas_model_indices_buffer,
+        );
+        buffers.counts = self.get_counts();
+
+        let allocator = application.allocator.allocator.lock().unwrap();
+        VulkanApp::clear_buffer(&allocator, &mut models_buffer.buffer);
+        VulkanApp::clear_buffer(&allocator, &mut tlas_nodes_buffer.buffer);
+        VulkanApp::clear_buffer(&allocator, &mut tlas_model_indices_buffer.buffer);
+        Ok(())
+    }
+
+    /// Records the upload of the current camera to the gpu
+    pub fn update_camera_buffer(&self, device: &Device, command_buffer: &CommandBuffer) {
+        if let (Some(camera), Some(buffers)) = (&self.camera, &self.buffers) {
+            let camera = [camera.get_gpu_data()];
+            buffers
+                .camera_buffer
+                .update_elements(&camera, device, command_buffer);
         }
     }
 }
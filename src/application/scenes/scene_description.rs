@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+
+use glam::{EulerRot, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::application::error::{Error, Result};
+
+/// Content of a `.ron` scene file
+/// The paths are relative to the directory of the scene file
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    pub materials: Vec<MaterialDescription>,
+    pub meshes: Vec<MeshDescription>,
+    /// Stored and saved again, but not rendered yet
+    /// Only the sky and the emissive materials light the scene
+    pub lights: Vec<LightDescription>,
+    /// Equirectangular image lighting the scene from every direction
+    /// Stored and saved again, but not rendered yet
+    pub environment_map: Option<PathBuf>,
+}
+
+/// Pose and projection of the camera, the angles are in degrees
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraDescription {
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        Self {
+            position: [0., 0., -5.],
+            yaw: -90.,
+            pitch: 0.,
+            fov: 45.,
+            near: 0.1,
+            far: 200.,
+        }
+    }
+}
+
+/// A material that the meshes reference by its name
+/// The textures are PNG, JPEG or HDR files, their texels are multiplied by the factors
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaterialDescription {
+    pub name: String,
+    #[serde(default = "default_albedo")]
+    pub albedo: [f32; 4],
+    #[serde(default)]
+    pub emissive: [f32; 3],
+    #[serde(default = "default_roughness")]
+    pub roughness: f32,
+    #[serde(default)]
+    pub metalness: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub albedo_texture: Option<PathBuf>,
+    /// Tangent space normal map
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normal_texture: Option<PathBuf>,
+    /// Roughness in the green channel and metalness in the blue one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roughness_metalness_texture: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emissive_texture: Option<PathBuf>,
+}
+
+fn default_albedo() -> [f32; 4] {
+    [1., 1., 1., 1.]
+}
+
+fn default_roughness() -> f32 {
+    1.
+}
+
+impl MaterialDescription {
+    fn get_textures_mut(&mut self) -> [&mut Option<PathBuf>; 4] {
+        [
+            &mut self.albedo_texture,
+            &mut self.normal_texture,
+            &mut self.roughness_metalness_texture,
+            &mut self.emissive_texture,
+        ]
+    }
+}
+
+/// An OBJ or glTF file placed in the scene
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeshDescription {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub transform: TransformDescription,
+    /// Name of the material replacing the materials of the file
+    #[serde(default)]
+    pub material: Option<String>,
+}
+
+/// Scale, then rotation in degrees around X, Y and Z, then translation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformDescription {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl Default for TransformDescription {
+    fn default() -> Self {
+        Self {
+            translation: [0., 0., 0.],
+            rotation: [0., 0., 0.],
+            scale: [1., 1., 1.],
+        }
+    }
+}
+
+impl TransformDescription {
+    pub fn get_matrix(&self) -> Mat4 {
+        let [x, y, z] = self.rotation.map(f32::to_radians);
+        Mat4::from_scale_rotation_translation(
+            Vec3::from_array(self.scale),
+            Quat::from_euler(EulerRot::XYZ, x, y, z),
+            Vec3::from_array(self.translation),
+        )
+    }
+}
+
+/// A light of the scene file, the renderer does not use it yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LightDescription {
+    Point {
+        position: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+    },
+    Directional {
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+    },
+}
+
+impl SceneDescription {
+    /// Describes a scene made of a single OBJ or glTF file
+    pub fn from_mesh_file(path: &Path) -> Self {
+        Self {
+            meshes: vec![MeshDescription {
+                path: path.to_path_buf(),
+                transform: Default::default(),
+                material: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// Reads a scene file, the paths it contains are made relative to the working directory
+    pub fn load(path: &Path) -> Result<Self> {
+        let scene_load_error = |message: String| Error::SceneLoad {
+            path: path.to_string_lossy().into_owned(),
+            message,
+        };
+        let content =
+            std::fs::read_to_string(path).map_err(|err| scene_load_error(err.to_string()))?;
+        // the ron errors contain the line and the column of the problem
+        let mut description: SceneDescription =
+            ron::from_str(&content).map_err(|err| scene_load_error(err.to_string()))?;
+        // the scene buffers cannot be empty
+        if description.meshes.is_empty() {
+            return Err(scene_load_error("the scene has no mesh".to_string()));
+        }
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        description.map_paths(|file_path| directory.join(file_path));
+        Ok(description)
+    }
+
+    /// Writes the scene file, the paths it contains are made relative to its directory when possible
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let scene_save_error = |message: String| Error::SceneSave {
+            path: path.to_string_lossy().into_owned(),
+            message,
+        };
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut description = self.clone();
+        description.map_paths(|file_path| {
+            file_path
+                .strip_prefix(directory)
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|_| file_path.to_path_buf())
+        });
+
+        let content = ron::ser::to_string_pretty(&description, ron::ser::PrettyConfig::default())
+            .map_err(|err| scene_save_error(err.to_string()))?;
+        std::fs::write(path, content).map_err(|err| scene_save_error(err.to_string()))
+    }
+
+    fn map_paths(&mut self, map: impl Fn(&Path) -> PathBuf) {
+        for mesh in self.meshes.iter_mut() {
+            mesh.path = map(&mesh.path);
+        }
+        for material in self.materials.iter_mut() {
+            for texture in material.get_textures_mut().into_iter().flatten() {
+                *texture = map(texture);
+            }
+        }
+        if let Some(environment_map) = self.environment_map.as_mut() {
+            *environment_map = map(environment_map);
+        }
+    }
+}
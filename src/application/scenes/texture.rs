@@ -0,0 +1,150 @@
+use std::path::Path;
+
+use ash::vk::Format;
+use half::f16;
+use image::{DynamicImage, ImageBuffer};
+
+use crate::application::error::{Error, Result};
+
+/// Texture index of the materials without a texture, the shaders test the same value
+pub const NO_TEXTURE: u32 = u32::MAX;
+
+/// How the shaders read the texels, the colors are stored in sRGB and the other data is linear
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+/// Texels of a texture, decoded when the scene is loaded and uploaded with their mipmaps
+pub struct Texture {
+    /// File or glTF image the texture was read from, for the logs
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// 8 bit RGBA for the LDR images, half float RGBA for the HDR ones
+    pub format: Format,
+    pub texels: Vec<u8>,
+}
+
+impl Texture {
+    /// Reads a PNG, JPEG or HDR file, the HDR images keep their range
+    pub fn load(path: &Path, color_space: ColorSpace) -> Result<Self> {
+        let image = image::open(path).map_err(|err| Error::SceneLoad {
+            path: path.to_string_lossy().into_owned(),
+            message: err.to_string(),
+        })?;
+        Ok(Self::from_image(
+            image,
+            path.to_string_lossy().into_owned(),
+            color_space,
+        ))
+    }
+
+    /// Converts an image decoded by the glTF importer
+    pub fn from_gltf(
+        image: &gltf::image::Data,
+        name: String,
+        color_space: ColorSpace,
+    ) -> Result<Self> {
+        use gltf::image::Format as GltfFormat;
+
+        let (width, height) = (image.width, image.height);
+        let pixels = &image.pixels;
+        let to_u16 = || -> Vec<u16> {
+            pixels
+                .chunks_exact(2)
+                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+                .collect()
+        };
+        let to_f32 = || -> Vec<f32> {
+            pixels
+                .chunks_exact(4)
+                .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect()
+        };
+
+        // the two channel images hold separate data, not a luminance and an alpha
+        let dynamic_image =
+            match image.format {
+                GltfFormat::R8 => ImageBuffer::from_raw(width, height, pixels.clone())
+                    .map(DynamicImage::ImageLuma8),
+                GltfFormat::R8G8 => {
+                    let texels = pixels
+                        .chunks_exact(2)
+                        .flat_map(|texel| [texel[0], texel[1], 0, u8::MAX])
+                        .collect();
+                    ImageBuffer::from_raw(width, height, texels).map(DynamicImage::ImageRgba8)
+                }
+                GltfFormat::R8G8B8 => ImageBuffer::from_raw(width, height, pixels.clone())
+                    .map(DynamicImage::ImageRgb8),
+                GltfFormat::R8G8B8A8 => ImageBuffer::from_raw(width, height, pixels.clone())
+                    .map(DynamicImage::ImageRgba8),
+                GltfFormat::R16 => {
+                    ImageBuffer::from_raw(width, height, to_u16()).map(DynamicImage::ImageLuma16)
+                }
+                GltfFormat::R16G16 => {
+                    let texels = to_u16()
+                        .chunks_exact(2)
+                        .flat_map(|texel| [texel[0], texel[1], 0, u16::MAX])
+                        .collect();
+                    ImageBuffer::from_raw(width, height, texels).map(DynamicImage::ImageRgba16)
+                }
+                GltfFormat::R16G16B16 => {
+                    ImageBuffer::from_raw(width, height, to_u16()).map(DynamicImage::ImageRgb16)
+                }
+                GltfFormat::R16G16B16A16 => {
+                    ImageBuffer::from_raw(width, height, to_u16()).map(DynamicImage::ImageRgba16)
+                }
+                GltfFormat::R32G32B32FLOAT => {
+                    ImageBuffer::from_raw(width, height, to_f32()).map(DynamicImage::ImageRgb32F)
+                }
+                GltfFormat::R32G32B32A32FLOAT => {
+                    ImageBuffer::from_raw(width, height, to_f32()).map(DynamicImage::ImageRgba32F)
+                }
+            };
+
+        let dynamic_image = dynamic_image.ok_or_else(|| Error::SceneLoad {
+            path: name.clone(),
+            message: String::from("the image is smaller than its size"),
+        })?;
+        Ok(Self::from_image(dynamic_image, name, color_space))
+    }
+
+    /// The float images are HDR and stored in half floats, the sRGB curve only applies to
+    /// the 8 bit images
+    fn from_image(image: DynamicImage, name: String, color_space: ColorSpace) -> Self {
+        let (width, height) = (image.width(), image.height());
+        let (format, texels) = match image {
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                let texels = image
+                    .to_rgba32f()
+                    .into_raw()
+                    .into_iter()
+                    .flat_map(|channel| f16::from_f32(channel).to_le_bytes())
+                    .collect();
+                (Format::R16G16B16A16_SFLOAT, texels)
+            }
+            _ => {
+                let format = match color_space {
+                    ColorSpace::Srgb => Format::R8G8B8A8_SRGB,
+                    ColorSpace::Linear => Format::R8G8B8A8_UNORM,
+                };
+                (format, image.to_rgba8().into_raw())
+            }
+        };
+
+        Self {
+            name,
+            width,
+            height,
+            format,
+            texels,
+        }
+    }
+
+    /// Number of levels of the full mipmap chain, down to a single texel
+    pub fn get_mip_levels(&self) -> u32 {
+        u32::BITS - self.width.max(self.height).max(1).leading_zeros()
+    }
+}
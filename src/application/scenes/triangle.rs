@@ -1,11 +1,20 @@
-use glam::Vec4;
+use glam::{Vec2, Vec4};
+use gpu_layout::GpuLayout;
 
-#[derive(Clone, Copy)]
+/// A triangle of a mesh, uploaded as is to the shaders
+/// The layout matches the `Triangle` struct in `scene/triangle.slang`
+#[repr(C)]
+#[derive(Clone, Copy, GpuLayout)]
 pub struct Triangle {
     pub p0: Vec4,
     pub p1: Vec4,
     pub p2: Vec4,
-    pub model_index: usize,
+    /// Texture coordinates of the vertices, the origin is the top left corner of the textures
+    pub uv0: Vec2,
+    pub uv1: Vec2,
+    pub uv2: Vec2,
+    pub mesh_index: u32,
+    pub _pad0: u32,
 }
 
 impl Default for Triangle {
@@ -14,7 +23,11 @@ impl Default for Triangle {
             p0: Vec4::from_array([-1., 0., 0., 1.]),
             p1: Vec4::from_array([1., 0., 0., 1.]),
             p2: Vec4::from_array([0., 1., 0., 1.]),
-            model_index: 0,
+            uv0: Vec2::new(0., 1.),
+            uv1: Vec2::new(1., 1.),
+            uv2: Vec2::new(0.5, 0.),
+            mesh_index: 0,
+            _pad0: 0,
         }
     }
 }
@@ -1,6 +1,6 @@
-use std::{collections::HashSet, mem::ManuallyDrop};
+use std::{collections::HashSet, mem::ManuallyDrop, path::PathBuf};
 
-use log::debug;
+use log::{debug, error};
 
 use ash::{
     ext::debug_utils,
@@ -27,8 +27,13 @@ use winit::{
 };
 
 use super::{
-    pipelines::pipeline::ComputePipeline,
+    error::{Error, Result},
+    pipelines::{
+        pipeline::ComputePipeline, vk_descriptors::DescriptorAllocator,
+        vk_shader_reload::ShaderWatcher,
+    },
     scenes::{camera::CameraMovement, scene::Scene},
+    vulkan::{vk_features::DeviceCapabilities, vk_readback::ImageReadback},
 };
 
 /// Structure to hold application parameters such as name, window width, and window height.
@@ -36,6 +41,28 @@ pub struct AppParameters {
     pub name: String,
     pub window_width: i32,
     pub window_height: i32,
+    /// Scene loaded at startup instead of the default one
+    pub scene_file: Option<PathBuf>,
+    /// Falls back to FIFO when the surface does not support it
+    pub present_mode: PresentModeKHR,
+    /// Initial number of samples traced per pixel every frame
+    pub samples_per_pixel: u32,
+    /// Renders this number of frames without a window instead of running the viewer
+    pub headless_frames: Option<u32>,
+    /// Image written at the end of the headless rendering, `.png` or `.exr`
+    pub headless_output: Option<PathBuf>,
+    /// Directory where the screenshots of the viewer are saved
+    pub screenshot_directory: PathBuf,
+    /// Enables the validation layer and the debug messenger
+    pub validation: ValidationMode,
+    /// Physical device used for the rendering
+    pub device_selection: DeviceSelection,
+    /// Compiled shaders overriding the ones embedded in the binary, the hot reload writes there
+    /// Defaults to the `target/shaders` of the checkout in debug builds
+    pub shader_directory: Option<PathBuf>,
+    /// Slang sources recompiled by the hot reload, which is disabled without them
+    /// Defaults to the `src/shaders` of the checkout in debug builds
+    pub shader_source_directory: Option<PathBuf>,
 }
 
 impl Default for AppParameters {
@@ -45,6 +72,73 @@ impl Default for AppParameters {
             name: String::from("Rulkan"),
             window_width: 1600,
             window_height: 912,
+            scene_file: None,
+            present_mode: PresentModeKHR::FIFO,
+            samples_per_pixel: 1,
+            headless_frames: None,
+            headless_output: None,
+            screenshot_directory: PathBuf::from("screenshots"),
+            validation: ValidationMode::from_env(),
+            device_selection: DeviceSelection::Best,
+            shader_directory: cfg!(debug_assertions)
+                .then(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/shaders")),
+            shader_source_directory: cfg!(debug_assertions)
+                .then(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/shaders")),
+        }
+    }
+}
+
+/// How to choose the physical device among the enumerated ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceSelection {
+    /// The suitable device with the best score
+    Best,
+    /// The device at this position in the enumeration order
+    Index(usize),
+    /// The first device whose name contains this text, ignoring the case
+    Name(String),
+}
+
+/// When to enable the validation layer and the debug messenger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationMode {
+    Disabled,
+    /// Skipped with a warning when the layer is not installed
+    IfAvailable,
+    /// The initialization fails when the layer is not installed
+    Required,
+}
+
+impl ValidationMode {
+    /// Reads `RULKAN_VALIDATION` (`off`, `on` or `required`)
+    /// Without it, the validation is enabled in debug builds only
+    pub fn from_env() -> Self {
+        match std::env::var("RULKAN_VALIDATION").as_deref() {
+            Ok("off" | "0") => Self::Disabled,
+            Ok("on" | "1") => Self::IfAvailable,
+            Ok("required") => Self::Required,
+            _ if cfg!(debug_assertions) => Self::IfAvailable,
+            _ => Self::Disabled,
+        }
+    }
+}
+
+/// Structure to hold the rendering parameters that can be changed at runtime.
+pub struct RenderParameters {
+    /// Traverse the bvh instead of testing every triangle
+    pub use_bvh: bool,
+    /// Number of diffuse bounces of each path
+    pub max_bounces: u32,
+    /// Number of paths traced per pixel every frame
+    pub samples_per_pixel: u32,
+}
+
+impl Default for RenderParameters {
+    fn default() -> Self {
+        Self {
+            use_bvh: true,
+            max_bounces: 4,
+            samples_per_pixel: 1,
         }
     }
 }
@@ -106,15 +200,19 @@ pub struct AllocatorWrapper {
 /// Main structure to hold Vulkan application components.
 pub struct VulkanApp {
     pub app_params: AppParameters,
+    pub render_params: RenderParameters,
     pub entry: Entry,
     pub instance: Instance,
-    pub debug_utils_loader: debug_utils::Instance,
+    /// Only loaded when the validation is enabled
+    pub debug_utils_loader: Option<debug_utils::Instance>,
     pub debug_call_back: vk::DebugUtilsMessengerEXT,
 
     pub surface: vk::SurfaceKHR,
     pub surface_loader: surface::Instance,
     pub physical_device: vk::PhysicalDevice,
     pub queue_families: QueueFamilyIndices,
+    /// Optional features enabled on the device
+    pub capabilities: DeviceCapabilities,
 
     pub device: Device,
 
@@ -124,15 +222,30 @@ pub struct VulkanApp {
     pub swapchain_image_format: Format,
     pub swapchain_extent: Extent2D,
     pub swapchain_image_views: Vec<ImageView>,
+    /// Set when the swapchain no longer matches the window
+    pub resize_requested: bool,
 
     pub frames: [FrameData; FRAME_OVERLAP],
+    /// Transient descriptor sets of each frame, freed when the frame starts again
+    pub frame_descriptors: [DescriptorAllocator; FRAME_OVERLAP],
     pub frame_number: usize,
     pub allocator: ManuallyDrop<AllocatorWrapper>,
 
     pub draw_image: AllocatedImage,
     pub draw_extent: Extent2D,
 
+    /// Sum of the samples of the frames rendered since the last reset
+    pub accumulation_image: AllocatedImage,
+    pub nb_accumulated_frames: u32,
+    /// Set by the screenshot hotkey and button, the frame is saved after being drawn
+    pub screenshot_requested: bool,
+    pub scene_save_requested: bool,
+
     pub pipelines: Vec<Box<dyn ComputePipeline>>,
+    /// Recompiles the shaders when their sources change, only in the viewer
+    pub shader_watcher: Option<ShaderWatcher>,
+    /// Error of the last shader reload, shown in the GUI
+    pub shader_error: Option<String>,
 
     pub gui_parameters: GuiParameters,
     pub immediate_submit: ImmediateSubmitStructures,
@@ -142,6 +255,15 @@ pub struct VulkanApp {
 
 pub const DEVICE_EXTENSION_NAMES_RAW: [*const i8; 1] = [swapchain::NAME.as_ptr()];
 
+/// Required device extensions, the swapchain is only needed to present to a surface
+pub fn get_device_extension_names(surface: &vk::SurfaceKHR) -> &'static [*const i8] {
+    if *surface == vk::SurfaceKHR::null() {
+        &[]
+    } else {
+        &DEVICE_EXTENSION_NAMES_RAW
+    }
+}
+
 pub struct QueueFamilyIndices {
     pub graphics_family: Option<u32>,
     pub graphics_queue: Queue,
@@ -180,7 +302,7 @@ impl VulkanApp {
         command_buffer: &CommandBuffer,
         window: &winit::window::Window,
         target_image_view: &ImageView,
-    ) {
+    ) -> Result<()> {
         // Generate UI
         self.gui_parameters
             .platform
@@ -190,18 +312,45 @@ impl VulkanApp {
                 self.gui_parameters.context.as_mut().unwrap().io_mut(),
                 window,
             )
-            .expect("Failed to prepare the GUI frame\n");
+            .map_err(|err| Error::Gui(err.to_string()))?;
         let ui = self.gui_parameters.context.as_mut().unwrap().frame();
 
         ui.window("Hello world")
-            .size([300.0, 110.0], imgui::Condition::FirstUseEver)
+            .size([300.0, 200.0], imgui::Condition::FirstUseEver)
             .build(|| {
                 ui.text_wrapped("Hello world!");
                 let mouse_pos = ui.io().mouse_pos;
                 ui.text(format!(
                     "Mouse Position: ({:.1},{:.1})",
                     mouse_pos[0], mouse_pos[1]
-                ))
+                ));
+                ui.text(format!(
+                    "Samples: {}",
+                    self.nb_accumulated_frames * self.render_params.samples_per_pixel
+                ));
+                let mut render_params_changed = false;
+                render_params_changed |= ui.checkbox("Use BVH", &mut self.render_params.use_bvh);
+                render_params_changed |=
+                    ui.slider("Max bounces", 0, 16, &mut self.render_params.max_bounces);
+                render_params_changed |= ui.slider(
+                    "Samples per frame",
+                    1,
+                    64,
+                    &mut self.render_params.samples_per_pixel,
+                );
+                if render_params_changed {
+                    self.nb_accumulated_frames = 0;
+                }
+                if ui.button("Screenshot (F12)") {
+                    self.screenshot_requested = true;
+                }
+                if ui.button("Save scene") {
+                    self.scene_save_requested = true;
+                }
+                if let Some(shader_error) = &self.shader_error {
+                    ui.text_colored([1.0, 0.3, 0.3, 1.0], "Shader reload failed:");
+                    ui.text_wrapped(shader_error);
+                }
             });
 
         self.gui_parameters
@@ -235,10 +384,11 @@ impl VulkanApp {
             .as_mut()
             .unwrap()
             .cmd_draw(*command_buffer, draw_data)
-            .unwrap();
+            .map_err(|err| Error::Gui(err.to_string()))?;
         unsafe {
             self.device.cmd_end_rendering(*command_buffer);
         }
+        Ok(())
     }
 
     pub fn draw_background(&mut self, command_buffer: &CommandBuffer) {
@@ -276,7 +426,120 @@ impl VulkanApp {
         self.pipelines = pipelines;
     }
 
-    pub fn draw(&mut self, window: &winit::window::Window) {
+    /// Records the rendering of the scene, the draw image is left in the general layout
+    pub fn render_draw_image(&mut self, command_buffer: &CommandBuffer) {
+        // transition our main draw image into general layout so we can write into it
+        // we will overwrite it all so we dont care about what was the older layout
+        Self::transition_image(
+            &self.device,
+            command_buffer,
+            &self.draw_image.image,
+            &ImageLayout::UNDEFINED,
+            &ImageLayout::GENERAL,
+        );
+
+        // the accumulation restarts when the scene changes
+        if self.scene.is_modified {
+            self.reset_accumulation();
+            self.scene.is_modified = false;
+        }
+
+        // the accumulation image keeps its content between frames unless the accumulation was reset
+        let accumulation_image_layout = if self.nb_accumulated_frames == 0 {
+            ImageLayout::UNDEFINED
+        } else {
+            ImageLayout::GENERAL
+        };
+        Self::transition_image(
+            &self.device,
+            command_buffer,
+            &self.accumulation_image.image,
+            &accumulation_image_layout,
+            &ImageLayout::GENERAL,
+        );
+
+        // background color
+        self.draw_background(command_buffer);
+        self.nb_accumulated_frames = self.nb_accumulated_frames.saturating_add(1);
+    }
+
+    /// Renders a frame in the draw image without presenting it
+    /// The draw image is left in the transfer source layout so it can be read back
+    pub fn draw_headless(&mut self) -> Result<()> {
+        let current_frame = *self.get_current_frame();
+
+        let fences = &[current_frame.render_fence];
+
+        // wait until the gpu has finished rendering the last frame
+        // without a timeout: a path traced frame can take seconds on a software rasterizer
+        unsafe {
+            self.device
+                .wait_for_fences(fences, true, u64::MAX)
+                .map_err(Error::vulkan("Failed to wait for the render fence"))?;
+            self.device
+                .reset_fences(fences)
+                .map_err(Error::vulkan("Failed to reset the render fence"))?;
+        }
+        self.reset_frame_descriptors()?;
+        self.update_scene_models()?;
+
+        let command_buffer = current_frame.command_buffer;
+        unsafe {
+            self.device
+                .reset_command_buffer(command_buffer, CommandBufferResetFlags::empty())
+                .map_err(Error::vulkan("Failed to reset the command buffer"))?
+        };
+
+        let command_buffer_begin_info =
+            CommandBufferBeginInfo::default().flags(CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+        self.draw_extent.width = self.draw_image.image_extent.width;
+        self.draw_extent.height = self.draw_image.image_extent.height;
+
+        unsafe {
+            self.device
+                .begin_command_buffer(command_buffer, &command_buffer_begin_info)
+                .map_err(Error::vulkan("Failed to begin the command buffer"))?
+        };
+
+        self.render_draw_image(&command_buffer);
+
+        Self::transition_image(
+            &self.device,
+            &command_buffer,
+            &self.draw_image.image,
+            &ImageLayout::GENERAL,
+            &ImageLayout::TRANSFER_SRC_OPTIMAL,
+        );
+
+        unsafe {
+            self.device
+                .end_command_buffer(command_buffer)
+                .map_err(Error::vulkan("Failed to end the command buffer"))?
+        };
+
+        // there is no swapchain to wait for or to signal
+        let command_buffer_submit_infos = [CommandBufferSubmitInfo::default()
+            .command_buffer(command_buffer)
+            .device_mask(0)];
+        let submit_infos =
+            [SubmitInfo2::default().command_buffer_infos(&command_buffer_submit_infos)];
+
+        unsafe {
+            self.device
+                .queue_submit2(
+                    self.queue_families.graphics_queue,
+                    &submit_infos,
+                    current_frame.render_fence,
+                )
+                .map_err(Error::vulkan("Failed to submit the command buffer"))?
+        };
+
+        self.frame_number += 1;
+        Ok(())
+    }
+
+    pub fn draw(&mut self, window: &winit::window::Window) -> Result<()> {
         let current_frame = *self.get_current_frame();
 
         let fences = &[current_frame.render_fence];
@@ -284,18 +547,46 @@ impl VulkanApp {
 
         // wait until the gpu has finished rendering the last frame. Timeout of 1 second
         unsafe {
-            self.device.wait_for_fences(fences, true, timeout).unwrap();
-            self.device.reset_fences(fences).unwrap();
+            self.device
+                .wait_for_fences(fences, true, timeout)
+                .map_err(Error::vulkan("Failed to wait for the render fence"))?;
         }
+        self.reset_frame_descriptors()?;
+        self.update_scene_models()?;
 
         //request image from the swapchain
         let swaphchain_semaphore = current_frame.swapchain_semaphore;
-        let swapchain_image_index = unsafe {
-            self.swapchain_loader
-                .acquire_next_image(self.swapchain, timeout, swaphchain_semaphore, Fence::null())
-                .unwrap()
-                .0 as usize
+        let acquire_result = unsafe {
+            self.swapchain_loader.acquire_next_image(
+                self.swapchain,
+                timeout,
+                swaphchain_semaphore,
+                Fence::null(),
+            )
         };
+        let swapchain_image_index = match acquire_result {
+            Ok((image_index, is_suboptimal)) => {
+                // a suboptimal image can still be presented, the swapchain is recreated after
+                self.resize_requested |= is_suboptimal;
+                image_index as usize
+            }
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.resize_requested = true;
+                return Ok(());
+            }
+            Err(err) => {
+                return Err(Error::vulkan("Failed to acquire the next swapchain image")(
+                    err,
+                ))
+            }
+        };
+
+        // only reset the fence when we are sure to submit work with it
+        unsafe {
+            self.device
+                .reset_fences(fences)
+                .map_err(Error::vulkan("Failed to reset the render fence"))?;
+        }
 
         let command_buffer = current_frame.command_buffer;
 
@@ -304,7 +595,7 @@ impl VulkanApp {
         unsafe {
             self.device
                 .reset_command_buffer(command_buffer, CommandBufferResetFlags::empty())
-                .unwrap()
+                .map_err(Error::vulkan("Failed to reset the command buffer"))?
         };
 
         // begin the command buffer recording. We will use this command buffer exactly once, so we want to let vulkan know that
@@ -318,21 +609,11 @@ impl VulkanApp {
         unsafe {
             self.device
                 .begin_command_buffer(command_buffer, &command_buffer_begin_info)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to begin the command buffer"))?
         };
 
-        // transition our main draw image into general layout so we can write into it
-        // we will overwrite it all so we dont care about what was the older layout
-        Self::transition_image(
-            &self.device,
-            &command_buffer,
-            &self.draw_image.image,
-            &ImageLayout::UNDEFINED,
-            &ImageLayout::GENERAL,
-        );
-
-        // background color
-        self.draw_background(&command_buffer);
+        // render the scene in the draw image
+        self.render_draw_image(&command_buffer);
 
         // transition the draw image and the swapchain image into their correct transfer layouts
         Self::transition_image(
@@ -371,7 +652,7 @@ impl VulkanApp {
         );
 
         let image_view = self.swapchain_image_views[swapchain_image_index];
-        self.draw_gui(&command_buffer, window, &image_view);
+        self.draw_gui(&command_buffer, window, &image_view)?;
 
         // set swapchain image layout to Present so we can show it on the screen
         Self::transition_image(
@@ -383,7 +664,11 @@ impl VulkanApp {
         );
 
         // finalize the command buffer
-        unsafe { self.device.end_command_buffer(command_buffer).unwrap() };
+        unsafe {
+            self.device
+                .end_command_buffer(command_buffer)
+                .map_err(Error::vulkan("Failed to end the command buffer"))?
+        };
 
         // prepare the submission to the queue
         // wait on the present semaphore (signaled when the swapchain is ready)
@@ -411,7 +696,7 @@ impl VulkanApp {
                     &submit_infos,
                     current_frame.render_fence,
                 )
-                .unwrap()
+                .map_err(Error::vulkan("Failed to submit the command buffer"))?
         };
 
         // prepare present
@@ -426,43 +711,118 @@ impl VulkanApp {
             .wait_semaphores(&present_info_wait_semaphores)
             .image_indices(&present_info_image_indices);
 
-        unsafe {
+        let present_result = unsafe {
             self.swapchain_loader
                 .queue_present(self.queue_families.graphics_queue, &present_info)
-                .unwrap();
+        };
+        match present_result {
+            Ok(false) => (),
+            Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.resize_requested = true,
+            Err(err) => return Err(Error::vulkan("Failed to present the swapchain image")(err)),
         }
 
         self.frame_number += 1;
+        Ok(())
+    }
+
+    /// Draws a frame in the window, recreating the swapchain first if needed
+    fn redraw(&mut self, window: &winit::window::Window) -> Result<()> {
+        if self.resize_requested {
+            self.recreate_swapchain(window)?;
+        }
+        self.reload_shaders()?;
+        self.draw(window)?;
+        if self.screenshot_requested {
+            self.save_screenshot()?;
+            self.screenshot_requested = false;
+        }
+        if self.scene_save_requested {
+            self.save_scene();
+            self.scene_save_requested = false;
+        }
+        Ok(())
+    }
+
+    /// Saves the scene next to the loaded scene file, as `scene.ron` without it
+    /// A failure is only logged so the viewer keeps running
+    pub fn save_scene(&self) {
+        let path = match &self.app_params.scene_file {
+            Some(scene_file) => scene_file.with_extension("ron"),
+            None => PathBuf::from("scene.ron"),
+        };
+        match self.scene.save_description(&path) {
+            Ok(()) => debug!("Saved the scene {:?}", path),
+            Err(err) => error!("{}", err),
+        }
+    }
+
+    /// Loads the scene of the parameters, the camera matches the size of the draw image
+    pub fn init_scene(&mut self) -> Result<()> {
+        self.scene.init(self.app_params.scene_file.as_deref())?;
+        if let Some(camera) = self.scene.camera.as_mut() {
+            let extent = self.draw_image.image_extent;
+            camera.aspect_ratio = extent.width as f32 / extent.height as f32;
+        }
+        Ok(())
+    }
+
+    /// Uploads the models added or moved since the previous frame
+    /// Only the top level acceleration structure is rebuilt, the meshes did not change
+    pub fn update_scene_models(&mut self) -> Result<()> {
+        if self.scene.tlas.is_some() || self.scene.buffers.is_none() {
+            return Ok(());
+        }
+
+        // the frames in flight read the buffers and the descriptors that are replaced
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .map_err(Error::vulkan("Failed to wait for the device"))?
+        };
+
+        // the uploaded triangles and meshes are only referenced by the bottom level structure
+        if self.scene.blas.is_none() {
+            return Err(Error::SceneUpload(String::from(
+                "meshes cannot be added after the scene is uploaded",
+            )));
+        }
+        self.scene.build_acceleration_structures();
+        let mut buffers = self.scene.buffers.take().unwrap();
+        let upload_result = self.scene.upload_model_buffers(&mut buffers, self);
+        self.scene.buffers = Some(buffers);
+        upload_result?;
+
+        // Take the pipelines out of self temporarily
+        let mut pipelines = std::mem::take(&mut self.pipelines);
+        let update_result = pipelines
+            .iter_mut()
+            .try_for_each(|pipeline| pipeline.update_model_descriptors(self));
+        self.pipelines = pipelines;
+        update_result
+    }
+
+    /// Restarts the progressive rendering from the next frame
+    pub fn reset_accumulation(&mut self) {
+        self.nb_accumulated_frames = 0;
     }
 
     pub fn camera_input_handler(&mut self, key_event: &KeyEvent) {
         let dt = 1. / 60.;
-        match key_event.key_without_modifiers().as_ref() {
-            Key::Character("W") => {
-                let camera = &mut self.scene.camera.as_mut().unwrap();
-                camera.process_keyboard(CameraMovement::FORWARD, dt);
-            }
-            Key::Character("S") => {
-                let camera = &mut self.scene.camera.as_mut().unwrap();
-                camera.process_keyboard(CameraMovement::BACKWARD, dt);
-            }
-            Key::Character("A") => {
-                let camera = &mut self.scene.camera.as_mut().unwrap();
-                camera.process_keyboard(CameraMovement::LEFT, dt);
-            }
-            Key::Character("D") => {
-                let camera = &mut self.scene.camera.as_mut().unwrap();
-                camera.process_keyboard(CameraMovement::RIGHT, dt);
-            }
-            Key::Named(NamedKey::ArrowUp) => {
-                let camera = &mut self.scene.camera.as_mut().unwrap();
-                camera.process_keyboard(CameraMovement::UP, dt);
-            }
-            Key::Named(NamedKey::ArrowDown) => {
-                let camera = &mut self.scene.camera.as_mut().unwrap();
-                camera.process_keyboard(CameraMovement::DOWN, dt);
-            }
-            _ => (),
+        let movement = match key_event.key_without_modifiers().as_ref() {
+            Key::Character("w" | "W") => Some(CameraMovement::FORWARD),
+            Key::Character("s" | "S") => Some(CameraMovement::BACKWARD),
+            Key::Character("a" | "A") => Some(CameraMovement::LEFT),
+            Key::Character("d" | "D") => Some(CameraMovement::RIGHT),
+            Key::Named(NamedKey::ArrowUp) => Some(CameraMovement::UP),
+            Key::Named(NamedKey::ArrowDown) => Some(CameraMovement::DOWN),
+            _ => None,
+        };
+
+        if let Some(movement) = movement {
+            let camera = &mut self.scene.camera.as_mut().unwrap();
+            camera.process_keyboard(movement, dt);
+            // the accumulated samples are from the previous point of view
+            self.reset_accumulation();
         }
     }
 
@@ -475,6 +835,11 @@ impl VulkanApp {
                     // Handle camera input
                     self.camera_input_handler(key_event);
 
+                    // Save the next rendered frame
+                    if let Key::Named(NamedKey::F12) = key_event.logical_key {
+                        self.screenshot_requested = true;
+                    }
+
                     // Handle Escape key to exit
                     if let Key::Named(NamedKey::Escape) = key_event.logical_key {
                         elwt.exit();
@@ -484,70 +849,134 @@ impl VulkanApp {
         }
     }
 
-    pub fn run(app_params: AppParameters) {
+    pub fn run(app_params: AppParameters) -> Result<()> {
+        if let Some(nb_frames) = app_params.headless_frames {
+            let output = app_params.headless_output.clone();
+            let readback = Self::run_headless(app_params, nb_frames)?;
+            debug!(
+                "Rendered {} headless frames of {}x{}",
+                nb_frames, readback.width, readback.height
+            );
+            if let Some(output) = output {
+                readback
+                    .save(&output)
+                    .map_err(|source| Error::ImageExport {
+                        path: output.to_string_lossy().into_owned(),
+                        source,
+                    })?;
+            }
+            return Ok(());
+        }
+
         debug!("Init Event Loop...");
-        let event_loop = Self::init_event_loop();
+        let event_loop = Self::init_event_loop()?;
         debug!("Ok\n");
         debug!("Init Window...");
-        let window = Self::init_window(&app_params, &event_loop);
+        let window = Self::init_window(&app_params, &event_loop)?;
         debug!("Ok\n");
 
-        let mut application = Self::init(app_params, &window);
+        let mut application = Self::init(app_params, Some(&window))?;
 
         // init the scene
         debug!("Init Scene...");
-        application.scene.init();
+        application.init_scene()?;
         debug!("Ok\n");
 
         // init the compute pipelines in the correct order
         debug!("Init Pipelines...");
-        application.init_immediate_submit_structures();
-        application.init_pipelines();
+        application.init_immediate_submit_structures()?;
+        application.init_pipelines()?;
+        application.init_shader_watcher();
         debug!("Ok\n");
 
         // init gui
         debug!("Init GUI...");
-        application.init_gui(&window);
+        application.init_gui(&window)?;
         debug!("Ok\n");
 
-        let _ = event_loop.run(move |event, elwt| {
-            // gui input handler
-            application
-                .gui_parameters
-                .platform
-                .as_mut()
-                .unwrap()
-                .handle_event(
-                    application
-                        .gui_parameters
-                        .context
-                        .as_mut()
-                        .unwrap()
-                        .io_mut(),
-                    &window,
-                    &event,
-                );
+        // an error while drawing stops the event loop and is returned once it exited
+        let mut frame_error = None;
+        event_loop
+            .run(|event, elwt| {
+                // gui input handler
+                application
+                    .gui_parameters
+                    .platform
+                    .as_mut()
+                    .unwrap()
+                    .handle_event(
+                        application
+                            .gui_parameters
+                            .context
+                            .as_mut()
+                            .unwrap()
+                            .io_mut(),
+                        &window,
+                        &event,
+                    );
+
+                // general handler
+                match event {
+                    Event::WindowEvent {
+                        event: WindowEvent::CloseRequested,
+                        ..
+                    } => {
+                        elwt.exit();
+                    }
+                    Event::AboutToWait => {
+                        window.request_redraw();
+                    }
+                    Event::WindowEvent {
+                        event: WindowEvent::Resized(_),
+                        ..
+                    } => {
+                        application.resize_requested = true;
+                    }
+                    Event::WindowEvent {
+                        event: WindowEvent::RedrawRequested,
+                        ..
+                    } => {
+                        // rendering is paused while the window is minimized
+                        let window_size = window.inner_size();
+                        if window_size.width > 0 && window_size.height > 0 {
+                            if let Err(err) = application.redraw(&window) {
+                                frame_error = Some(err);
+                                elwt.exit();
+                            }
+                        }
+                    }
+                    _ => (),
+                };
+                application.input_handler(&event, elwt);
+            })
+            .map_err(|err| Error::Window(err.to_string()))?;
+
+        match frame_error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
 
-            // general handler
-            match event {
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    ..
-                } => {
-                    elwt.exit();
-                }
-                Event::AboutToWait => {
-                    window.request_redraw();
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::RedrawRequested,
-                    ..
-                } => {
-                    application.draw(&window);
-                }
-                _ => (),
-            };
-            application.input_handler(&event, elwt);
-        });
+    /// Renders the given number of frames offscreen and reads the draw image back
+    /// No window, surface or swapchain is created so it runs on machines without a display
+    pub fn run_headless(app_params: AppParameters, nb_frames: u32) -> Result<ImageReadback> {
+        let mut application = Self::init(app_params, None)?;
+
+        // init the scene
+        debug!("Init Scene...");
+        application.init_scene()?;
+        debug!("Ok\n");
+
+        // init the compute pipelines in the correct order
+        debug!("Init Pipelines...");
+        application.init_immediate_submit_structures()?;
+        application.init_pipelines()?;
+        debug!("Ok\n");
+
+        for _ in 0..nb_frames {
+            application.draw_headless()?;
+        }
+
+        application.read_draw_image()
     }
 }
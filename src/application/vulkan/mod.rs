@@ -4,6 +4,7 @@ pub mod vk_commands;
 pub mod vk_debug;
 pub mod vk_device;
 pub mod vk_entry;
+pub mod vk_features;
 pub mod vk_frames;
 pub mod vk_gui;
 pub mod vk_image;
@@ -11,7 +12,9 @@ pub mod vk_immediate;
 pub mod vk_init;
 pub mod vk_instance;
 pub mod vk_physical_device;
+pub mod vk_readback;
 pub mod vk_surface;
 pub mod vk_swapchain;
 pub mod vk_sync_structures;
+pub mod vk_texture;
 pub mod vk_window;
@@ -1,18 +1,21 @@
 use ash::{vk::PhysicalDevice, Device, Instance};
 use vk_mem::{Allocator, AllocatorCreateInfo};
 
-use crate::application::vk_app::VulkanApp;
+use crate::application::{
+    error::{Error, Result},
+    vk_app::VulkanApp,
+};
 
 impl VulkanApp {
     pub fn init_allocator(
         instance: &Instance,
         device: &Device,
         physical_device: &PhysicalDevice,
-    ) -> Allocator {
+    ) -> Result<Allocator> {
         let allocator_info = AllocatorCreateInfo::new(instance, device, *physical_device);
         unsafe {
             Allocator::new(allocator_info)
-                .unwrap_or_else(|err| panic!("Failed to create the memory allocator: {:?}\n", err))
+                .map_err(Error::vulkan("Failed to create the memory allocator"))
         }
     }
 }
@@ -1,8 +1,16 @@
-use ash::vk::{BufferCopy, BufferCreateInfo, BufferUsageFlags, DeviceAddress};
+use ash::vk::{
+    AccessFlags2, BufferCopy, BufferCreateInfo, BufferMemoryBarrier2, BufferUsageFlags,
+    CommandBuffer, DependencyInfo, DeviceAddress, PipelineStageFlags2, WHOLE_SIZE,
+};
+use ash::Device;
+use gpu_layout::GpuLayout;
 use vk_mem::Alloc;
 use vk_mem::{AllocationCreateFlags, AllocationCreateInfo, MemoryUsage};
 
-use crate::application::vk_app::{AllocatedBuffer, VulkanApp};
+use crate::application::{
+    error::{Error, Result},
+    vk_app::{AllocatedBuffer, VulkanApp},
+};
 
 pub struct BufferGPU {
     pub buffer: AllocatedBuffer,
@@ -15,7 +23,7 @@ impl VulkanApp {
         alloc_size: usize,
         usage_flags: BufferUsageFlags,
         memory_usage: MemoryUsage,
-    ) -> AllocatedBuffer {
+    ) -> Result<AllocatedBuffer> {
         let buffer_create_info = BufferCreateInfo::default()
             .size(alloc_size as u64)
             .usage(usage_flags);
@@ -30,10 +38,10 @@ impl VulkanApp {
             let allocator = self.allocator.allocator.lock().unwrap();
             allocator
                 .create_buffer(&buffer_create_info, &allocation_create_info)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to allocate the buffer"))?
         };
 
-        AllocatedBuffer { buffer, allocation }
+        Ok(AllocatedBuffer { buffer, allocation })
     }
 
     pub fn clear_buffer(allocator: &vk_mem::Allocator, buffer: &mut AllocatedBuffer) {
@@ -44,16 +52,28 @@ impl VulkanApp {
 }
 
 impl BufferGPU {
-    pub fn upload_elements<T>(elements: &[T], application: &VulkanApp) -> BufferGPU {
+    pub fn upload_elements<T: GpuLayout>(
+        elements: &[T],
+        application: &VulkanApp,
+    ) -> Result<BufferGPU> {
+        Self::upload_elements_with_usage(elements, BufferUsageFlags::STORAGE_BUFFER, application)
+    }
+
+    /// Copies the elements to a new buffer, their layout is the one the shaders read
+    pub fn upload_elements_with_usage<T: GpuLayout>(
+        elements: &[T],
+        usage_flags: BufferUsageFlags,
+        application: &VulkanApp,
+    ) -> Result<BufferGPU> {
         let size = std::mem::size_of_val(elements);
 
         // create triangles buffer
         let buffer = application.create_buffer(
             size,
-            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::TRANSFER_DST,
+            usage_flags | BufferUsageFlags::TRANSFER_DST,
             // | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
             vk_mem::MemoryUsage::GpuOnly,
-        );
+        )?;
 
         // // find the address of the buffer
         // let buffer_address_info = BufferDeviceAddressInfo::default()
@@ -75,7 +95,7 @@ impl BufferGPU {
             size,
             BufferUsageFlags::TRANSFER_SRC,
             vk_mem::MemoryUsage::CpuOnly,
-        );
+        )?;
 
         // Lock the allocator and map the staging buffer
         {
@@ -87,8 +107,7 @@ impl BufferGPU {
             // copy buffers
             unsafe {
                 let data_slice = std::slice::from_raw_parts_mut(data, size);
-                let input_slice = std::slice::from_raw_parts(elements.as_ptr() as *const u8, size);
-                data_slice.copy_from_slice(input_slice);
+                data_slice.copy_from_slice(T::as_bytes(elements));
             };
         } // The lock on the allocator is released here
 
@@ -102,7 +121,7 @@ impl BufferGPU {
             unsafe {
                 device.cmd_copy_buffer(cmd, staging.buffer, surface.buffer.buffer, &elements_copy);
             };
-        });
+        })?;
 
         // Lock the allocator again to destroy the staging buffer
         VulkanApp::clear_buffer(
@@ -110,6 +129,49 @@ impl BufferGPU {
             &mut staging,
         );
 
-        surface
+        Ok(surface)
+    }
+
+    /// Records an inline update of the buffer content
+    /// The elements must be at most 65536 bytes and their size a multiple of 4
+    pub fn update_elements<T: GpuLayout>(
+        &self,
+        elements: &[T],
+        device: &Device,
+        command_buffer: &CommandBuffer,
+    ) {
+        let data = T::as_bytes(elements);
+
+        // wait for the previous shader reads before overwriting the buffer
+        let before_update_barrier = [BufferMemoryBarrier2::default()
+            .src_stage_mask(PipelineStageFlags2::COMPUTE_SHADER)
+            .src_access_mask(AccessFlags2::NONE)
+            .dst_stage_mask(PipelineStageFlags2::TRANSFER)
+            .dst_access_mask(AccessFlags2::TRANSFER_WRITE)
+            .buffer(self.buffer.buffer)
+            .offset(0)
+            .size(WHOLE_SIZE)];
+
+        // make the new content visible to the compute shaders
+        let after_update_barrier = [BufferMemoryBarrier2::default()
+            .src_stage_mask(PipelineStageFlags2::TRANSFER)
+            .src_access_mask(AccessFlags2::TRANSFER_WRITE)
+            .dst_stage_mask(PipelineStageFlags2::COMPUTE_SHADER)
+            .dst_access_mask(AccessFlags2::SHADER_READ | AccessFlags2::UNIFORM_READ)
+            .buffer(self.buffer.buffer)
+            .offset(0)
+            .size(WHOLE_SIZE)];
+
+        unsafe {
+            device.cmd_pipeline_barrier2(
+                *command_buffer,
+                &DependencyInfo::default().buffer_memory_barriers(&before_update_barrier),
+            );
+            device.cmd_update_buffer(*command_buffer, self.buffer.buffer, 0, data);
+            device.cmd_pipeline_barrier2(
+                *command_buffer,
+                &DependencyInfo::default().buffer_memory_barriers(&after_update_barrier),
+            );
+        }
     }
 }
@@ -6,23 +6,26 @@ use ash::{
     Device,
 };
 
-use crate::application::vk_app::{FrameData, QueueFamilyIndices, VulkanApp, FRAME_OVERLAP};
+use crate::application::{
+    error::{Error, Result},
+    vk_app::{FrameData, QueueFamilyIndices, VulkanApp, FRAME_OVERLAP},
+};
 
 impl VulkanApp {
     pub fn init_commands(
         device: &Device,
         queue_families: &QueueFamilyIndices,
         frames: &mut [FrameData; FRAME_OVERLAP],
-    ) {
+    ) -> Result<()> {
         let command_pool_info = CommandPoolCreateInfo::default()
             .flags(CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
             .queue_family_index(queue_families.graphics_family.unwrap());
 
-        frames.iter_mut().for_each(|frame| {
+        for frame in frames.iter_mut() {
             frame.command_pool = unsafe {
                 device
                     .create_command_pool(&command_pool_info, None)
-                    .unwrap()
+                    .map_err(Error::vulkan("Failed to create a command pool"))?
             };
             let command_buffer_allocate_info = CommandBufferAllocateInfo::default()
                 .command_pool(frame.command_pool)
@@ -31,8 +34,9 @@ impl VulkanApp {
             frame.command_buffer = unsafe {
                 device
                     .allocate_command_buffers(&command_buffer_allocate_info)
-                    .unwrap()[0]
+                    .map_err(Error::vulkan("Failed to allocate a command buffer"))?[0]
             };
-        });
+        }
+        Ok(())
     }
 }
@@ -1,10 +1,15 @@
 use std::{borrow::Cow, ffi::CStr};
 
 use ash::{ext::debug_utils, vk, Entry, Instance};
+use log::{log, Level};
 
-use crate::application::vk_app::VulkanApp;
+use crate::application::{
+    error::{Error, Result},
+    vk_app::VulkanApp,
+};
 
 /// Callback function for Vulkan debug messages.
+/// The messages are logged with the `vulkan` target, at the level matching their severity.
 unsafe extern "system" fn vulkan_debug_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_type: vk::DebugUtilsMessageTypeFlagsEXT,
@@ -26,25 +31,38 @@ unsafe extern "system" fn vulkan_debug_callback(
         CStr::from_ptr(callback_data.p_message).to_string_lossy()
     };
 
-    println!(
-        "{message_severity:?}:\n{message_type:?} [{message_id_name} ({message_id_number})] : {message}\n",
+    let level = if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+        Level::Error
+    } else if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+        Level::Warn
+    } else if message_severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+        Level::Info
+    } else {
+        Level::Trace
+    };
+
+    log!(
+        target: "vulkan",
+        level,
+        "{message_type:?} [{message_id_name} ({message_id_number})] : {message}",
     );
 
     vk::FALSE
 }
 
 impl VulkanApp {
-    /// Sets up the Vulkan debug callback for error, warning, and info messages.
+    /// Sets up the Vulkan debug callback for all the message severities, `RUST_LOG` filters them.
     pub fn init_debug_callback(
         entry: &Entry,
         instance: &Instance,
-    ) -> (debug_utils::Instance, vk::DebugUtilsMessengerEXT) {
+    ) -> Result<(debug_utils::Instance, vk::DebugUtilsMessengerEXT)> {
         // Setup debug callback
         let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
             .message_severity(
                 vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
                     | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
             )
             .message_type(
                 vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
@@ -57,15 +75,17 @@ impl VulkanApp {
         let debug_call_back = unsafe {
             debug_utils_loader
                 .create_debug_utils_messenger(&debug_info, None)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to create the debug messenger"))?
         };
-        (debug_utils_loader, debug_call_back)
+        Ok((debug_utils_loader, debug_call_back))
     }
 
     pub fn clear_debug_callback(&self) {
-        unsafe {
-            self.debug_utils_loader
-                .destroy_debug_utils_messenger(self.debug_call_back, None);
+        // there is no messenger when the validation is disabled
+        if let Some(debug_utils_loader) = &self.debug_utils_loader {
+            unsafe {
+                debug_utils_loader.destroy_debug_utils_messenger(self.debug_call_back, None);
+            }
         }
     }
 }
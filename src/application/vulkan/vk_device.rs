@@ -5,21 +5,48 @@ use ash::{
     Device, Instance,
 };
 
-use crate::application::vk_app::{QueueFamilyIndices, VulkanApp, DEVICE_EXTENSION_NAMES_RAW};
+use crate::application::{
+    error::{Error, Result},
+    vk_app::{get_device_extension_names, QueueFamilyIndices, VulkanApp},
+};
+
+use super::vk_features::{DeviceCapabilities, RAY_QUERY_EXTENSION_NAMES};
 
 impl VulkanApp {
+    /// Creates the device with the required features, and the optional ones it supports
+    /// The required features are checked by `get_device_rejection_reasons`
     pub fn init_device(
         physical_device: &PhysicalDevice,
         instance: &Instance,
+        surface: &vk::SurfaceKHR,
+        capabilities: &DeviceCapabilities,
         queue_families: &mut QueueFamilyIndices,
-    ) -> Device {
-        let features = vk::PhysicalDeviceFeatures::default().shader_clip_distance(true);
+    ) -> Result<Device> {
+        let features = vk::PhysicalDeviceFeatures::default()
+            .shader_clip_distance(true)
+            .shader_float64(capabilities.shader_float64);
         let mut features_12 = vk::PhysicalDeviceVulkan12Features::default()
             .buffer_device_address(true)
-            .descriptor_indexing(true);
+            .descriptor_indexing(true)
+            // the arrays of descriptors declared without a size
+            .runtime_descriptor_array(true)
+            .descriptor_binding_partially_bound(true)
+            .descriptor_binding_variable_descriptor_count(true)
+            // the materials of neighbouring pixels read different textures
+            .shader_sampled_image_array_non_uniform_indexing(true);
         let mut features_13 = vk::PhysicalDeviceVulkan13Features::default()
             .synchronization2(true)
             .dynamic_rendering(true);
+        let mut ray_query_features =
+            vk::PhysicalDeviceRayQueryFeaturesKHR::default().ray_query(true);
+        let mut acceleration_structure_features =
+            vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default()
+                .acceleration_structure(true);
+
+        let mut extension_names = get_device_extension_names(surface).to_vec();
+        if capabilities.ray_query {
+            extension_names.extend(RAY_QUERY_EXTENSION_NAMES.iter().map(|name| name.as_ptr()));
+        }
 
         let priority = [1.0];
         let mut queue_create_infos: Vec<DeviceQueueCreateInfo> = Vec::new();
@@ -32,17 +59,22 @@ impl VulkanApp {
             queue_create_infos.push(queue_info);
         }
 
-        let device_create_info = vk::DeviceCreateInfo::default()
+        let mut device_create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(&queue_create_infos)
-            .enabled_extension_names(&DEVICE_EXTENSION_NAMES_RAW)
+            .enabled_extension_names(&extension_names)
             .enabled_features(&features)
             .push_next(&mut features_12)
             .push_next(&mut features_13);
+        if capabilities.ray_query {
+            device_create_info = device_create_info
+                .push_next(&mut ray_query_features)
+                .push_next(&mut acceleration_structure_features);
+        }
 
         let device = unsafe {
             instance
                 .create_device(*physical_device, &device_create_info, None)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to create the device"))?
         };
 
         queue_families.graphics_queue =
@@ -51,7 +83,7 @@ impl VulkanApp {
         queue_families.present_queue =
             unsafe { device.get_device_queue(queue_families.present_family.unwrap(), 0) };
 
-        device
+        Ok(device)
     }
 
     pub fn clear_device(&self) {
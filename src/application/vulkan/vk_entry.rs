@@ -1,13 +1,13 @@
 use ash::Entry;
 
-use crate::application::vk_app::VulkanApp;
+use crate::application::{
+    error::{Error, Result},
+    vk_app::VulkanApp,
+};
 
 impl VulkanApp {
     /// Loads the Vulkan library entry points.
-    pub fn init_entry() -> Entry {
-        unsafe {
-            Entry::load()
-                .unwrap_or_else(|err| panic!("Failed to load the vulkan library: {:?}", err))
-        }
+    pub fn init_entry() -> Result<Entry> {
+        unsafe { Entry::load().map_err(|err| Error::LibraryLoad(err.to_string())) }
     }
 }
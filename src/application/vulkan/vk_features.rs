@@ -0,0 +1,107 @@
+use std::ffi::CStr;
+
+use ash::{
+    khr::{acceleration_structure, deferred_host_operations, ray_query},
+    vk, Instance,
+};
+
+use crate::application::vk_app::VulkanApp;
+
+/// Optional features of the device, enabled when they are supported
+/// The pipelines consult them to choose their shader variants
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DeviceCapabilities {
+    /// `VK_KHR_ray_query` and the acceleration structures it needs
+    pub ray_query: bool,
+    /// 64 bit floats in the shaders
+    pub shader_float64: bool,
+}
+
+/// Device extensions needed by the ray queries
+pub const RAY_QUERY_EXTENSION_NAMES: [&CStr; 3] = [
+    ray_query::NAME,
+    acceleration_structure::NAME,
+    deferred_host_operations::NAME,
+];
+
+impl VulkanApp {
+    /// Lists the features enabled by `init_device` that the device does not support
+    /// The device must support Vulkan 1.3 to be queried
+    pub fn get_missing_features(
+        instance: &Instance,
+        physical_device: &vk::PhysicalDevice,
+    ) -> Vec<&'static str> {
+        let mut features_12 = vk::PhysicalDeviceVulkan12Features::default();
+        let mut features_13 = vk::PhysicalDeviceVulkan13Features::default();
+        let mut features = vk::PhysicalDeviceFeatures2::default()
+            .push_next(&mut features_12)
+            .push_next(&mut features_13);
+        unsafe { instance.get_physical_device_features2(*physical_device, &mut features) };
+        let shader_clip_distance = features.features.shader_clip_distance;
+
+        [
+            ("shaderClipDistance", shader_clip_distance),
+            ("bufferDeviceAddress", features_12.buffer_device_address),
+            ("descriptorIndexing", features_12.descriptor_indexing),
+            (
+                "runtimeDescriptorArray",
+                features_12.runtime_descriptor_array,
+            ),
+            (
+                "descriptorBindingPartiallyBound",
+                features_12.descriptor_binding_partially_bound,
+            ),
+            (
+                "descriptorBindingVariableDescriptorCount",
+                features_12.descriptor_binding_variable_descriptor_count,
+            ),
+            (
+                "shaderSampledImageArrayNonUniformIndexing",
+                features_12.shader_sampled_image_array_non_uniform_indexing,
+            ),
+            ("synchronization2", features_13.synchronization2),
+            ("dynamicRendering", features_13.dynamic_rendering),
+        ]
+        .into_iter()
+        .filter(|&(_, is_supported)| is_supported == vk::FALSE)
+        .map(|(name, _)| name)
+        .collect()
+    }
+
+    /// Detects the optional features supported by the device
+    pub fn query_device_capabilities(
+        instance: &Instance,
+        physical_device: &vk::PhysicalDevice,
+    ) -> DeviceCapabilities {
+        let extension_properties = unsafe {
+            instance
+                .enumerate_device_extension_properties(*physical_device)
+                .unwrap_or_default()
+        };
+        let are_ray_query_extensions_found = RAY_QUERY_EXTENSION_NAMES.iter().all(|&required| {
+            extension_properties
+                .iter()
+                .any(|found| found.extension_name_as_c_str() == Ok(required))
+        });
+
+        let mut ray_query_features = vk::PhysicalDeviceRayQueryFeaturesKHR::default();
+        let mut acceleration_structure_features =
+            vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
+        let mut features = vk::PhysicalDeviceFeatures2::default();
+        // the structures of unsupported extensions must not be chained
+        if are_ray_query_extensions_found {
+            features = features
+                .push_next(&mut ray_query_features)
+                .push_next(&mut acceleration_structure_features);
+        }
+        unsafe { instance.get_physical_device_features2(*physical_device, &mut features) };
+        let shader_float64 = features.features.shader_float64 == vk::TRUE;
+
+        DeviceCapabilities {
+            ray_query: are_ray_query_extensions_found
+                && ray_query_features.ray_query == vk::TRUE
+                && acceleration_structure_features.acceleration_structure == vk::TRUE,
+            shader_float64,
+        }
+    }
+}
@@ -1,4 +1,16 @@
-use crate::application::vk_app::{FrameData, VulkanApp, FRAME_OVERLAP};
+use ash::{
+    vk::{DescriptorSet, DescriptorSetLayout, DescriptorType},
+    Device,
+};
+
+use crate::application::{
+    error::Result,
+    pipelines::vk_descriptors::{DescriptorAllocator, PoolSizeRatio},
+    vk_app::{FrameData, VulkanApp, FRAME_OVERLAP},
+};
+
+/// Sets of the first pool of each frame, the next pools are larger
+const FRAME_DESCRIPTOR_SETS: u32 = 16;
 
 impl VulkanApp {
     pub fn get_current_frame(&self) -> &FrameData {
@@ -10,7 +22,57 @@ impl VulkanApp {
         (frames, 0)
     }
 
-    pub fn clear_frames(&self) {
+    /// Creates the allocators of the transient descriptor sets of each frame
+    /// The ratios cover the descriptor types declared by the shaders
+    pub fn init_frame_descriptors(device: &Device) -> Result<[DescriptorAllocator; FRAME_OVERLAP]> {
+        let pool_ratios = [
+            PoolSizeRatio {
+                descriptor_type: DescriptorType::STORAGE_IMAGE,
+                ratio: 2.,
+            },
+            PoolSizeRatio {
+                descriptor_type: DescriptorType::STORAGE_BUFFER,
+                ratio: 8.,
+            },
+            PoolSizeRatio {
+                descriptor_type: DescriptorType::UNIFORM_BUFFER,
+                ratio: 1.,
+            },
+            PoolSizeRatio {
+                descriptor_type: DescriptorType::SAMPLED_IMAGE,
+                ratio: 4.,
+            },
+            PoolSizeRatio {
+                descriptor_type: DescriptorType::SAMPLER,
+                ratio: 1.,
+            },
+        ];
+        let mut frame_descriptors: [DescriptorAllocator; FRAME_OVERLAP] = Default::default();
+        for allocator in frame_descriptors.iter_mut() {
+            allocator.init_pool(device, FRAME_DESCRIPTOR_SETS, &pool_ratios)?;
+        }
+        Ok(frame_descriptors)
+    }
+
+    /// Frees the transient descriptor sets of the current frame, its fence must be signaled
+    pub fn reset_frame_descriptors(&mut self) -> Result<()> {
+        let frame_index = self.frame_number % FRAME_OVERLAP;
+        self.frame_descriptors[frame_index].reset_pools(&self.device)
+    }
+
+    /// Allocates a descriptor set used until the current frame starts again
+    pub fn allocate_frame_descriptor_set(
+        &mut self,
+        descriptor_set_layout: &DescriptorSetLayout,
+    ) -> Result<DescriptorSet> {
+        let frame_index = self.frame_number % FRAME_OVERLAP;
+        self.frame_descriptors[frame_index].allocate(&self.device, descriptor_set_layout)
+    }
+
+    pub fn clear_frames(&mut self) {
+        for allocator in self.frame_descriptors.iter_mut() {
+            allocator.destroy_pools(&self.device);
+        }
         for &frame in self.frames.iter() {
             unsafe {
                 self.device
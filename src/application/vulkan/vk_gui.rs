@@ -1,23 +1,18 @@
-use std::sync::Mutex;
-
 use ash::vk::{
     DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorType,
 };
-use once_cell::sync::Lazy;
 use winit::window::Window;
 
-use crate::application::vk_app::VulkanApp;
-
-static FONT_DATA_GLOBAL: Lazy<Mutex<Vec<u8>>> = Lazy::new(|| Mutex::new(load_font_data()));
+use crate::application::{
+    error::{Error, Result},
+    vk_app::VulkanApp,
+};
 
-fn load_font_data() -> Vec<u8> {
-    let crate_path = env!("CARGO_MANIFEST_DIR");
-    let font_path = format!("{}/src/assets/fonts/Roboto-Regular.ttf", crate_path);
-    std::fs::read(font_path).expect("Failed to read font file")
-}
+/// Embedded so that the binary does not depend on the checkout
+static FONT_DATA: &[u8] = include_bytes!("../../assets/fonts/Roboto-Regular.ttf");
 
 impl VulkanApp {
-    pub fn init_gui(&mut self, window: &Window) {
+    pub fn init_gui(&mut self, window: &Window) -> Result<()> {
         // create descriptor pool for IMGUI
         let descriptor_pool_sizes = [
             DescriptorPoolSize::default()
@@ -63,7 +58,7 @@ impl VulkanApp {
         self.gui_parameters.descriptor_pool = unsafe {
             self.device
                 .create_descriptor_pool(&descriptor_pool_create_info, None)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to create the GUI descriptor pool"))?
         };
 
         // initialize gui library
@@ -80,7 +75,7 @@ impl VulkanApp {
                 }),
             },
             imgui::FontSource::TtfData {
-                data: FONT_DATA_GLOBAL.lock().unwrap().as_slice(),
+                data: FONT_DATA,
                 size_pixels: font_size,
                 config: Some(imgui::FontConfig::default()),
             },
@@ -110,11 +105,12 @@ impl VulkanApp {
                 ..Default::default()
             }),
         )
-        .unwrap();
+        .map_err(|err| Error::Gui(err.to_string()))?;
 
         self.gui_parameters.renderer = Some(renderer);
         self.gui_parameters.platform = Some(platform);
         self.gui_parameters.context = Some(imgui);
+        Ok(())
     }
 
     pub fn clear_gui(&mut self) {
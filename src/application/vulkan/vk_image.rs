@@ -10,7 +10,10 @@ use ash::{
 };
 use vk_mem::{Alloc, AllocationCreateInfo, Allocator};
 
-use crate::application::vk_app::{AllocatedImage, AppParameters, VulkanApp};
+use crate::application::{
+    error::{Error, Result},
+    vk_app::{AllocatedImage, AppParameters, VulkanApp},
+};
 
 impl VulkanApp {
     pub fn transition_image(
@@ -103,36 +106,27 @@ impl VulkanApp {
         }
     }
 
-    pub fn init_images(
-        app_params: &AppParameters,
+    /// Creates a 2D image in gpu local memory and its image view
+    /// The view covers every mip level
+    pub fn create_image(
         device: &Device,
         allocator: &Allocator,
-    ) -> AllocatedImage {
-        //draw image size will match the window
-        let draw_image_extent = Extent3D::default()
-            .width(app_params.window_width as u32)
-            .height(app_params.window_height as u32)
-            .depth(1);
-
-        // hardcoding the draw format to 32 bit float
-        let draw_image_format = Format::R16G16B16A16_SFLOAT;
-        let draw_image_usages = ImageUsageFlags::default()
-            | ImageUsageFlags::TRANSFER_SRC
-            | ImageUsageFlags::TRANSFER_DST
-            | ImageUsageFlags::STORAGE
-            | ImageUsageFlags::COLOR_ATTACHMENT;
-
+        extent: Extent3D,
+        format: Format,
+        usages: ImageUsageFlags,
+        mip_levels: u32,
+    ) -> Result<AllocatedImage> {
         let image_info = ImageCreateInfo::default()
             .image_type(ImageType::TYPE_2D)
-            .format(draw_image_format)
-            .extent(draw_image_extent)
-            .usage(draw_image_usages)
-            .mip_levels(1)
+            .format(format)
+            .extent(extent)
+            .usage(usages)
+            .mip_levels(mip_levels)
             .array_layers(1)
             .samples(SampleCountFlags::TYPE_1)
             .tiling(ImageTiling::OPTIMAL);
 
-        // for the draw image, we want to allocate it from gpu local memory
+        // we want to allocate it from gpu local memory
         let image_allocation_info = AllocationCreateInfo {
             required_flags: MemoryPropertyFlags::DEVICE_LOCAL,
             ..Default::default()
@@ -142,13 +136,13 @@ impl VulkanApp {
         let (image, allocation) = unsafe {
             allocator
                 .create_image(&image_info, &image_allocation_info)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to allocate the image"))?
         };
 
-        // build a image-view for the draw image to use for rendering
+        // build a image-view for the image to use for rendering
         let image_subresource_range = ImageSubresourceRange::default()
             .base_mip_level(0)
-            .level_count(1)
+            .level_count(mip_levels)
             .base_array_layer(0)
             .layer_count(1)
             .aspect_mask(ImageAspectFlags::COLOR);
@@ -156,17 +150,74 @@ impl VulkanApp {
         let image_view_info = ImageViewCreateInfo::default()
             .view_type(ImageViewType::TYPE_2D)
             .image(image)
-            .format(draw_image_format)
+            .format(format)
             .subresource_range(image_subresource_range);
 
-        let image_view = unsafe { device.create_image_view(&image_view_info, None).unwrap() };
+        let image_view = unsafe {
+            device
+                .create_image_view(&image_view_info, None)
+                .map_err(Error::vulkan("Failed to create the image view"))?
+        };
 
-        AllocatedImage {
+        Ok(AllocatedImage {
             image,
             image_view,
-            image_extent: draw_image_extent,
-            image_format: draw_image_format,
+            image_extent: extent,
+            image_format: format,
             allocation,
+        })
+    }
+
+    /// Creates the draw image and the accumulation image
+    pub fn init_images(
+        app_params: &AppParameters,
+        device: &Device,
+        allocator: &Allocator,
+    ) -> Result<(AllocatedImage, AllocatedImage)> {
+        //draw image size will match the window
+        let draw_image_extent = Extent3D::default()
+            .width(app_params.window_width as u32)
+            .height(app_params.window_height as u32)
+            .depth(1);
+
+        // hardcoding the draw format to 16 bit float
+        let draw_image_format = Format::R16G16B16A16_SFLOAT;
+        let draw_image_usages = ImageUsageFlags::default()
+            | ImageUsageFlags::TRANSFER_SRC
+            | ImageUsageFlags::TRANSFER_DST
+            | ImageUsageFlags::STORAGE
+            | ImageUsageFlags::COLOR_ATTACHMENT;
+
+        let draw_image = Self::create_image(
+            device,
+            allocator,
+            draw_image_extent,
+            draw_image_format,
+            draw_image_usages,
+            1,
+        )?;
+
+        // the accumulation image sums the samples over the frames so it needs 32 bit floats
+        let accumulation_image_format = Format::R32G32B32A32_SFLOAT;
+        let accumulation_image_usages = ImageUsageFlags::STORAGE;
+
+        let accumulation_image = Self::create_image(
+            device,
+            allocator,
+            draw_image_extent,
+            accumulation_image_format,
+            accumulation_image_usages,
+            1,
+        )?;
+
+        Ok((draw_image, accumulation_image))
+    }
+
+    /// Destroys an image created by `create_image` and its view
+    pub fn clear_image(device: &Device, allocator: &Allocator, image: &mut AllocatedImage) {
+        unsafe {
+            device.destroy_image_view(image.image_view, None);
+            allocator.destroy_image(image.image, &mut image.allocation);
         }
     }
 
@@ -175,12 +226,10 @@ impl VulkanApp {
             for &image_view in self.swapchain_image_views.iter() {
                 self.device.destroy_image_view(image_view, None);
             }
-
-            self.device
-                .destroy_image_view(self.draw_image.image_view, None);
-
-            let allocator = self.allocator.allocator.lock().unwrap();
-            allocator.destroy_image(self.draw_image.image, &mut self.draw_image.allocation);
         }
+
+        let allocator = self.allocator.allocator.lock().unwrap();
+        Self::clear_image(&self.device, &allocator, &mut self.draw_image);
+        Self::clear_image(&self.device, &allocator, &mut self.accumulation_image);
     }
 }
@@ -4,10 +4,13 @@ use ash::vk::{
     CommandPoolCreateInfo, FenceCreateFlags, FenceCreateInfo, SubmitInfo2,
 };
 
-use crate::application::vk_app::VulkanApp;
+use crate::application::{
+    error::{Error, Result},
+    vk_app::VulkanApp,
+};
 
 impl VulkanApp {
-    pub fn init_immediate_submit_structures(&mut self) {
+    pub fn init_immediate_submit_structures(&mut self) -> Result<()> {
         let command_pool_info = CommandPoolCreateInfo::default()
             .flags(CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
             .queue_family_index(self.queue_families.graphics_family.unwrap());
@@ -16,7 +19,7 @@ impl VulkanApp {
             self.immediate_submit.command_pool = self
                 .device
                 .create_command_pool(&command_pool_info, None)
-                .unwrap();
+                .map_err(Error::vulkan("Failed to create the immediate command pool"))?;
         }
 
         let allocate_info = CommandBufferAllocateInfo::default()
@@ -27,14 +30,19 @@ impl VulkanApp {
             self.immediate_submit.command_buffer = self
                 .device
                 .allocate_command_buffers(&allocate_info)
-                .unwrap()[0]
+                .map_err(Error::vulkan(
+                    "Failed to allocate the immediate command buffer",
+                ))?[0]
         }
 
         let fence_create_info = FenceCreateInfo::default().flags(FenceCreateFlags::SIGNALED);
         unsafe {
-            self.immediate_submit.fence =
-                self.device.create_fence(&fence_create_info, None).unwrap()
+            self.immediate_submit.fence = self
+                .device
+                .create_fence(&fence_create_info, None)
+                .map_err(Error::vulkan("Failed to create the immediate fence"))?
         }
+        Ok(())
     }
 
     pub fn clear_immediate_submit_structures(&mut self) {
@@ -45,17 +53,19 @@ impl VulkanApp {
         }
     }
 
-    pub fn immediate_submit(&self, fct: &dyn Fn(&Self, CommandBuffer)) {
+    pub fn immediate_submit(&self, fct: &dyn Fn(&Self, CommandBuffer)) -> Result<()> {
         unsafe {
             self.device
                 .reset_fences(&[self.immediate_submit.fence])
-                .unwrap();
+                .map_err(Error::vulkan("Failed to reset the immediate fence"))?;
             self.device
                 .reset_command_buffer(
                     self.immediate_submit.command_buffer,
                     CommandBufferResetFlags::empty(),
                 )
-                .unwrap();
+                .map_err(Error::vulkan(
+                    "Failed to reset the immediate command buffer",
+                ))?;
         }
 
         let command_buffer_begin_info =
@@ -67,7 +77,9 @@ impl VulkanApp {
                     self.immediate_submit.command_buffer,
                     &command_buffer_begin_info,
                 )
-                .unwrap();
+                .map_err(Error::vulkan(
+                    "Failed to begin the immediate command buffer",
+                ))?;
         }
 
         fct(self, self.immediate_submit.command_buffer);
@@ -75,7 +87,7 @@ impl VulkanApp {
         unsafe {
             self.device
                 .end_command_buffer(self.immediate_submit.command_buffer)
-                .unwrap();
+                .map_err(Error::vulkan("Failed to end the immediate command buffer"))?;
         }
 
         let command_buffer_submit_info =
@@ -94,10 +106,13 @@ impl VulkanApp {
                     &submit_info,
                     self.immediate_submit.fence,
                 )
-                .unwrap();
+                .map_err(Error::vulkan(
+                    "Failed to submit the immediate command buffer",
+                ))?;
             self.device
                 .wait_for_fences(&[self.immediate_submit.fence], true, 9999999999)
-                .unwrap();
+                .map_err(Error::vulkan("Failed to wait for the immediate fence"))?;
         }
+        Ok(())
     }
 }
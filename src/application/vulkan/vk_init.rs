@@ -3,29 +3,42 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use crate::application::vk_app::{AllocatorWrapper, AppParameters, VulkanApp};
+use crate::application::{
+    error::Result,
+    vk_app::{AllocatorWrapper, AppParameters, RenderParameters, VulkanApp},
+};
 
-use ash::vk::Extent2D;
+use ash::vk::{DebugUtilsMessengerEXT, Extent2D, SurfaceKHR};
 use log::debug;
 use winit::window::Window;
 
 impl VulkanApp {
     /// Creates a new VulkanApp instance and initializes Vulkan components.
-    pub fn init(app_params: AppParameters, window: &Window) -> VulkanApp {
+    /// Without a window, the surface and the swapchain are skipped to render offscreen.
+    pub fn init(app_params: AppParameters, window: Option<&Window>) -> Result<VulkanApp> {
         debug!("Init Entry...");
-        let entry = Self::init_entry();
+        let entry = Self::init_entry()?;
         debug!("Ok\n");
 
         debug!("Init Instance...");
-        let instance = Self::init_instance(&app_params, window, &entry);
+        let (instance, is_validation_enabled) = Self::init_instance(&app_params, window, &entry)?;
         debug!("Ok\n");
 
-        debug!("Init Debug Callback...");
-        let (debug_utils_loader, debug_call_back) = Self::init_debug_callback(&entry, &instance);
-        debug!("Ok\n");
+        let (debug_utils_loader, debug_call_back) = if is_validation_enabled {
+            debug!("Init Debug Callback...");
+            let (debug_utils_loader, debug_call_back) =
+                Self::init_debug_callback(&entry, &instance)?;
+            debug!("Ok\n");
+            (Some(debug_utils_loader), debug_call_back)
+        } else {
+            (None, DebugUtilsMessengerEXT::null())
+        };
 
         debug!("Init Surface...");
-        let surface = Self::init_surface(&entry, &instance, window);
+        let surface = match window {
+            Some(window) => Self::init_surface(&entry, &instance, window)?,
+            None => SurfaceKHR::null(),
+        };
         debug!("Ok\n");
 
         debug!("Init Surface Loader...");
@@ -33,12 +46,23 @@ impl VulkanApp {
         debug!("Ok\n");
 
         debug!("Init Physical Device and Queue Families...");
-        let (physical_device, mut queue_families) =
-            Self::init_physical_device_and_queue_families(&instance, &surface, &surface_loader);
+        let (physical_device, mut queue_families, capabilities) =
+            Self::init_physical_device_and_queue_families(
+                &app_params,
+                &instance,
+                &surface,
+                &surface_loader,
+            )?;
         debug!("Ok\n");
 
         debug!("Init Device...");
-        let device = Self::init_device(&physical_device, &instance, &mut queue_families);
+        let device = Self::init_device(
+            &physical_device,
+            &instance,
+            &surface,
+            &capabilities,
+            &mut queue_families,
+        )?;
         debug!("Ok\n");
 
         debug!("Init Swapchain...");
@@ -49,14 +73,17 @@ impl VulkanApp {
             swapchain_image_format,
             swapchain_extent,
             swapchain_image_views,
-        ) = Self::init_swapchain(
-            &app_params,
-            &instance,
-            &device,
-            &surface,
-            &surface_loader,
-            &physical_device,
-        );
+        ) = match window {
+            Some(_) => Self::init_swapchain(
+                &app_params,
+                &instance,
+                &device,
+                &surface,
+                &surface_loader,
+                &physical_device,
+            )?,
+            None => Self::init_headless_swapchain(&app_params, &instance, &device)?,
+        };
         debug!("Ok\n");
 
         debug!("Init Frames...");
@@ -64,27 +91,37 @@ impl VulkanApp {
         debug!("Ok\n");
 
         debug!("Init Commands...");
-        Self::init_commands(&device, &queue_families, &mut frames);
+        Self::init_commands(&device, &queue_families, &mut frames)?;
         debug!("Ok\n");
 
         debug!("Init Sync Structures...");
-        Self::init_sync_structuress(&device, &mut frames);
+        Self::init_sync_structuress(&device, &mut frames)?;
         debug!("Ok\n");
 
         // check frames
         frames.iter().for_each(|frame| frame.check());
 
+        debug!("Init Frame Descriptors...");
+        let frame_descriptors = Self::init_frame_descriptors(&device)?;
+        debug!("Ok\n");
+
         debug!("Init Memory Allocator...");
-        let allocator = Self::init_allocator(&instance, &device, &physical_device);
+        let allocator = Self::init_allocator(&instance, &device, &physical_device)?;
         debug!("Ok\n");
 
         debug!("Init Images...");
-        let draw_image = Self::init_images(&app_params, &device, &allocator);
+        let (draw_image, accumulation_image) = Self::init_images(&app_params, &device, &allocator)?;
         let draw_extent = Extent2D::default();
         debug!("Ok\n");
 
-        VulkanApp {
+        let render_params = RenderParameters {
+            samples_per_pixel: app_params.samples_per_pixel.max(1),
+            ..Default::default()
+        };
+
+        Ok(VulkanApp {
             app_params,
+            render_params,
             entry,
             instance,
             debug_utils_loader,
@@ -93,6 +130,7 @@ impl VulkanApp {
             surface_loader,
             physical_device,
             queue_families,
+            capabilities,
             device,
             swapchain_loader,
             swapchain,
@@ -100,18 +138,26 @@ impl VulkanApp {
             swapchain_image_format,
             swapchain_extent,
             swapchain_image_views,
+            resize_requested: false,
             frames,
+            frame_descriptors,
             frame_number,
             allocator: ManuallyDrop::new(AllocatorWrapper {
                 allocator: Arc::new(Mutex::new(allocator)),
             }),
             draw_image,
             draw_extent,
+            accumulation_image,
+            nb_accumulated_frames: 0,
+            screenshot_requested: false,
+            scene_save_requested: false,
             pipelines: Vec::new(),
+            shader_watcher: None,
+            shader_error: None,
             gui_parameters: Default::default(),
             immediate_submit: Default::default(),
             scene: Default::default(),
-        }
+        })
     }
 }
 
@@ -119,11 +165,12 @@ impl Drop for VulkanApp {
     fn drop(&mut self) {
         debug!("Cleaning Everything...\n");
         unsafe {
-            self.device.device_wait_idle().unwrap();
+            // nothing more can be done if the device is lost while dropping
+            let _ = self.device.device_wait_idle();
         }
         {
             let scene = &mut self.scene;
-            scene.clear(&self.allocator.allocator.lock().unwrap());
+            scene.clear(&self.device, &self.allocator.allocator.lock().unwrap());
         };
         self.clear_gui();
         self.clear_immediate_submit_structures();
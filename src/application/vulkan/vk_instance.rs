@@ -1,10 +1,13 @@
-use log::debug;
+use log::{debug, warn};
 
 use winit::{raw_window_handle::HasDisplayHandle, window::Window};
 
 use std::ffi::{c_char, CStr, CString};
 
-use crate::application::vk_app::{AppParameters, VulkanApp};
+use crate::application::{
+    error::{Error, Result},
+    vk_app::{AppParameters, ValidationMode, VulkanApp},
+};
 use ash::{
     ext::debug_utils,
     vk::{self, API_VERSION_1_3},
@@ -13,59 +16,103 @@ use ash::{
 
 impl VulkanApp {
     /// Initializes the Vulkan instance with the required extensions and layers.
-    pub fn init_instance(app_params: &AppParameters, window: &Window, entry: &Entry) -> Instance {
+    /// Without a window, the surface extensions are not required.
+    /// Returns whether the validation layer and the debug utils extension are enabled.
+    pub fn init_instance(
+        app_params: &AppParameters,
+        window: Option<&Window>,
+        entry: &Entry,
+    ) -> Result<(Instance, bool)> {
+        let available_extensions = unsafe {
+            entry
+                .enumerate_instance_extension_properties(None)
+                .map_err(Error::vulkan(
+                    "Failed to enumerate the available extensions",
+                ))?
+        };
+        let available_layers = unsafe {
+            entry
+                .enumerate_instance_layer_properties()
+                .map_err(Error::vulkan("Failed to enumerate the available layers"))?
+        };
+
+        // Enable the validation only when it is requested and installed
+        let validation_layer_cstr =
+            unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0") };
+        let is_validation_available = available_layers
+            .iter()
+            .any(|available| available.layer_name_as_c_str() == Ok(validation_layer_cstr))
+            && available_extensions
+                .iter()
+                .any(|available| available.extension_name_as_c_str() == Ok(debug_utils::NAME));
+        let is_validation_enabled = match app_params.validation {
+            ValidationMode::Disabled => false,
+            ValidationMode::IfAvailable if !is_validation_available => {
+                warn!("The validation layer is not installed, the validation is disabled");
+                false
+            }
+            // a missing layer or extension is reported by the checks below
+            ValidationMode::IfAvailable | ValidationMode::Required => true,
+        };
+
         // Get the required extensions
-        let mut required_extensions = ash_window::enumerate_required_extensions(
-            window
-                .display_handle()
-                .unwrap_or_else(|err| panic!("Failed to get the required extensions: {:?}\n", err))
-                .as_raw(),
-        )
-        .unwrap()
-        .to_vec();
-        required_extensions.push(debug_utils::NAME.as_ptr());
+        let mut required_extensions = match window {
+            Some(window) => ash_window::enumerate_required_extensions(
+                window
+                    .display_handle()
+                    .map_err(|err| Error::Window(err.to_string()))?
+                    .as_raw(),
+            )
+            .map_err(Error::vulkan("Failed to get the required extensions"))?
+            .to_vec(),
+            None => Vec::new(),
+        };
+        if is_validation_enabled {
+            required_extensions.push(debug_utils::NAME.as_ptr());
+        }
         debug!("Extensions:");
         for extension in &required_extensions {
             let extension_name = unsafe { CStr::from_ptr(*extension).to_string_lossy() };
             debug!("\t{}", extension_name);
         }
+        for &required in &required_extensions {
+            let required = unsafe { CStr::from_ptr(required) };
+            let is_available = available_extensions
+                .iter()
+                .any(|available| available.extension_name_as_c_str() == Ok(required));
+            if !is_available {
+                return Err(Error::MissingExtension(
+                    required.to_string_lossy().into_owned(),
+                ));
+            }
+        }
 
         // Get the required layers
-        let required_layers_cstr = [
-            unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0") },
-            // unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_RENDERDOC_Capture\0") },
-        ];
+        let mut required_layers_cstr = Vec::new();
+        if is_validation_enabled {
+            required_layers_cstr.push(validation_layer_cstr);
+        }
+        // required_layers_cstr.push(unsafe { CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_RENDERDOC_Capture\0") });
         let required_layers: Vec<*const c_char> = required_layers_cstr
             .iter()
             .map(|raw_name| raw_name.as_ptr())
             .collect();
         debug!("Layers:");
-        for layer in required_layers_cstr {
+        for layer in &required_layers_cstr {
             debug!("\t{:?}", layer);
         }
-        let available_layers = unsafe {
-            entry
-                .enumerate_instance_layer_properties()
-                .unwrap_or_else(|err| {
-                    panic!("Failed to enumerate the available layers: {:?}\n", err)
-                })
-        };
-        for required in required_layers_cstr {
-            let mut is_available = false;
-            'inner: for available in &available_layers {
-                let name = available.layer_name_as_c_str().unwrap();
-                if name == required {
-                    is_available = true;
-                    break 'inner;
-                }
-            }
+        for &required in &required_layers_cstr {
+            let is_available = available_layers
+                .iter()
+                .any(|available| available.layer_name_as_c_str() == Ok(required));
             if !is_available {
-                panic!("The required layer {:?} is not available!\n", required);
+                return Err(Error::MissingLayer(required.to_string_lossy().into_owned()));
             }
         }
 
         // Init the Vulkan instance
-        let binding = CString::new(app_params.name.clone()).unwrap();
+        // a name with a nul byte cannot be given to vulkan
+        let binding = CString::new(app_params.name.clone()).unwrap_or_default();
         let application_info = vk::ApplicationInfo::default()
             .application_name(binding.as_c_str())
             .api_version(API_VERSION_1_3);
@@ -74,11 +121,12 @@ impl VulkanApp {
             .application_info(&application_info)
             .enabled_extension_names(&required_extensions)
             .enabled_layer_names(&required_layers);
-        unsafe {
+        let instance = unsafe {
             entry
                 .create_instance(&create_instance_info, None)
-                .unwrap_or_else(|err| panic!("Failed to create the instance: {:?}\n", err))
-        }
+                .map_err(Error::vulkan("Failed to create the instance"))?
+        };
+        Ok((instance, is_validation_enabled))
     }
 
     pub fn clear_instance(&self) {
@@ -1,8 +1,38 @@
 use std::ffi::CStr;
 
-use crate::application::vk_app::{QueueFamilyIndices, VulkanApp, DEVICE_EXTENSION_NAMES_RAW};
+use crate::application::{
+    error::{Error, Result},
+    vk_app::{
+        get_device_extension_names, AppParameters, DeviceSelection, QueueFamilyIndices, VulkanApp,
+    },
+};
 
 use ash::{khr::surface, vk, Instance};
+use log::debug;
+
+use super::vk_features::DeviceCapabilities;
+
+/// Properties of a physical device and whether it can run the application
+pub struct PhysicalDeviceReport {
+    pub physical_device: vk::PhysicalDevice,
+    /// Position in the enumeration order
+    pub index: usize,
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub api_version: u32,
+    /// Size of the device local heaps in bytes
+    pub device_local_memory: u64,
+    pub score: u64,
+    /// Why the device cannot run the application, empty when it is suitable
+    pub rejection_reasons: Vec<String>,
+    pub capabilities: DeviceCapabilities,
+}
+
+impl PhysicalDeviceReport {
+    pub fn is_suitable(&self) -> bool {
+        self.rejection_reasons.is_empty()
+    }
+}
 
 impl VulkanApp {
     pub fn find_queue_families(
@@ -26,13 +56,15 @@ impl VulkanApp {
                     let support_graphics = queue_family_properties
                         .queue_flags
                         .contains(vk::QueueFlags::GRAPHICS);
-                    let support_present = surface_loader
-                        .get_physical_device_surface_support(
-                            *physical_device,
-                            index as u32,
-                            *surface,
-                        )
-                        .unwrap();
+                    // without a surface nothing is presented
+                    let support_present = *surface == vk::SurfaceKHR::null()
+                        || surface_loader
+                            .get_physical_device_surface_support(
+                                *physical_device,
+                                index as u32,
+                                *surface,
+                            )
+                            .unwrap_or(false);
                     if support_graphics && support_present {
                         queue_families.present_family = Some(index as u32);
                         queue_families.graphics_family = Some(index as u32);
@@ -41,69 +73,224 @@ impl VulkanApp {
                         None
                     }
                 })
-                .expect("Failed to find a suitable")
         };
+        // the families stay incomplete when no queue can both render and present
         queue_families
     }
 
-    pub fn is_device_suitable(
+    /// Lists why the device cannot run the application, the device is suitable when it is empty
+    pub fn get_device_rejection_reasons(
         physical_device: &vk::PhysicalDevice,
         instance: &Instance,
         surface: &vk::SurfaceKHR,
         surface_loader: &surface::Instance,
-    ) -> bool {
-        let are_queue_families_completed =
-            Self::find_queue_families(physical_device, instance, surface, surface_loader)
-                .is_complete();
-        let are_extensions_found = Self::check_device_extension_support(instance, physical_device);
-        let is_swap_chain_supported =
-            Self::query_swapchain_support(surface, surface_loader, physical_device).is_complete();
-        are_queue_families_completed && are_extensions_found && is_swap_chain_supported
+    ) -> Vec<String> {
+        let mut rejection_reasons = Vec::new();
+        if !Self::find_queue_families(physical_device, instance, surface, surface_loader)
+            .is_complete()
+        {
+            rejection_reasons.push("no queue family supports both graphics and present".into());
+        }
+        if !Self::check_device_extension_support(instance, physical_device, surface) {
+            rejection_reasons.push("missing a required device extension".into());
+        }
+        // the features of a version are only queried when the device supports it
+        let api_version =
+            unsafe { instance.get_physical_device_properties(*physical_device) }.api_version;
+        if api_version < vk::API_VERSION_1_3 {
+            rejection_reasons.push(format!(
+                "supports Vulkan {}.{} but 1.3 is required",
+                vk::api_version_major(api_version),
+                vk::api_version_minor(api_version)
+            ));
+        } else {
+            for feature in Self::get_missing_features(instance, physical_device) {
+                rejection_reasons.push(format!("missing the feature {}", feature));
+            }
+        }
+        let is_swap_chain_supported = *surface == vk::SurfaceKHR::null()
+            || Self::query_swapchain_support(surface, surface_loader, physical_device)
+                .is_ok_and(|swapchain_support| swapchain_support.is_complete());
+        if !is_swap_chain_supported {
+            rejection_reasons.push("no surface format or present mode".into());
+        }
+        rejection_reasons
     }
 
-    pub fn init_physical_device_and_queue_families(
+    /// Ranks the suitable devices, the device type matters more than the memory size
+    fn score_device(device_type: vk::PhysicalDeviceType, device_local_memory: u64) -> u64 {
+        let type_score = match device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+            vk::PhysicalDeviceType::CPU => 1,
+            _ => 0,
+        };
+        // the memory is counted in MiB, below 2^40 MiB it never outweighs the type
+        (type_score << 40) + (device_local_memory >> 20)
+    }
+
+    /// Describes every physical device in the enumeration order
+    pub fn get_physical_device_reports(
         instance: &Instance,
         surface: &vk::SurfaceKHR,
         surface_loader: &surface::Instance,
-    ) -> (vk::PhysicalDevice, QueueFamilyIndices) {
-        let physical_device = Self::init_physical_devices(instance)
-            .iter()
-            .find_map(|physical_device| {
-                let is_suitable =
-                    Self::is_device_suitable(physical_device, instance, surface, surface_loader);
-                if is_suitable {
-                    Some(*physical_device)
-                } else {
-                    None
+    ) -> Result<Vec<PhysicalDeviceReport>> {
+        let reports = Self::init_physical_devices(instance)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, physical_device)| {
+                let properties =
+                    unsafe { instance.get_physical_device_properties(physical_device) };
+                let memory_properties =
+                    unsafe { instance.get_physical_device_memory_properties(physical_device) };
+                let device_local_memory = memory_properties
+                    .memory_heaps_as_slice()
+                    .iter()
+                    .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+                    .map(|heap| heap.size)
+                    .sum();
+                PhysicalDeviceReport {
+                    physical_device,
+                    index,
+                    name: properties
+                        .device_name_as_c_str()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    device_type: properties.device_type,
+                    api_version: properties.api_version,
+                    device_local_memory,
+                    score: Self::score_device(properties.device_type, device_local_memory),
+                    rejection_reasons: Self::get_device_rejection_reasons(
+                        &physical_device,
+                        instance,
+                        surface,
+                        surface_loader,
+                    ),
+                    capabilities: Self::query_device_capabilities(instance, &physical_device),
                 }
             })
-            .expect("Failed to find a suitable physical device!\n");
-        (
+            .collect();
+        Ok(reports)
+    }
+
+    /// Chooses the device requested by the parameters among the reports
+    pub fn select_physical_device<'a>(
+        reports: &'a [PhysicalDeviceReport],
+        device_selection: &DeviceSelection,
+    ) -> Result<&'a PhysicalDeviceReport> {
+        let report = match device_selection {
+            DeviceSelection::Best => {
+                return reports
+                    .iter()
+                    .filter(|report| report.is_suitable())
+                    .max_by_key(|report| report.score)
+                    .ok_or(Error::NoSuitableDevice)
+            }
+            DeviceSelection::Index(index) => reports
+                .get(*index)
+                .ok_or_else(|| Error::DeviceNotFound(format!("the index {}", index)))?,
+            DeviceSelection::Name(name) => {
+                let name_lowercase = name.to_lowercase();
+                reports
+                    .iter()
+                    .find(|report| report.name.to_lowercase().contains(&name_lowercase))
+                    .ok_or_else(|| Error::DeviceNotFound(format!("the name `{}'", name)))?
+            }
+        };
+        if !report.is_suitable() {
+            return Err(Error::DeviceRejected {
+                name: report.name.clone(),
+                reasons: report.rejection_reasons.clone(),
+            });
+        }
+        Ok(report)
+    }
+
+    pub fn init_physical_device_and_queue_families(
+        app_params: &AppParameters,
+        instance: &Instance,
+        surface: &vk::SurfaceKHR,
+        surface_loader: &surface::Instance,
+    ) -> Result<(vk::PhysicalDevice, QueueFamilyIndices, DeviceCapabilities)> {
+        let reports = Self::get_physical_device_reports(instance, surface, surface_loader)?;
+        let report = Self::select_physical_device(&reports, &app_params.device_selection)?;
+        debug!("Selected the physical device {}", report.name);
+        debug!("Capabilities: {:?}", report.capabilities);
+        let physical_device = report.physical_device;
+        Ok((
             physical_device,
             Self::find_queue_families(&physical_device, instance, surface, surface_loader),
-        )
+            report.capabilities,
+        ))
     }
 
-    pub fn init_physical_devices(instance: &Instance) -> Vec<vk::PhysicalDevice> {
+    /// Prints the properties of every physical device and why the unsuitable ones are rejected
+    /// Without a window, the support of the presentation is not checked
+    pub fn list_devices(app_params: &AppParameters) -> Result<()> {
+        let entry = Self::init_entry()?;
+        let (instance, _) = Self::init_instance(app_params, None, &entry)?;
+        let surface_loader = Self::init_surface_loader(&entry, &instance);
+
+        let reports =
+            Self::get_physical_device_reports(&instance, &vk::SurfaceKHR::null(), &surface_loader);
+        if let Ok(reports) = &reports {
+            Self::print_device_reports(reports, &app_params.device_selection);
+        }
+
+        unsafe { instance.destroy_instance(None) };
+        reports.map(|_| ())
+    }
+
+    fn print_device_reports(reports: &[PhysicalDeviceReport], device_selection: &DeviceSelection) {
+        let selected_index = Self::select_physical_device(reports, device_selection)
+            .ok()
+            .map(|report| report.index);
+        for report in reports {
+            let selected = if Some(report.index) == selected_index {
+                " (selected)"
+            } else {
+                ""
+            };
+            println!("[{}] {}{}", report.index, report.name, selected);
+            println!("\ttype: {:?}", report.device_type);
+            println!(
+                "\tapi version: {}.{}.{}",
+                vk::api_version_major(report.api_version),
+                vk::api_version_minor(report.api_version),
+                vk::api_version_patch(report.api_version)
+            );
+            println!("\tdevice memory: {} MiB", report.device_local_memory >> 20);
+            println!("\tscore: {}", report.score);
+            println!("\tray query: {}", report.capabilities.ray_query);
+            println!("\tshader float64: {}", report.capabilities.shader_float64);
+            for reason in &report.rejection_reasons {
+                println!("\trejected: {}", reason);
+            }
+        }
+    }
+
+    pub fn init_physical_devices(instance: &Instance) -> Result<Vec<vk::PhysicalDevice>> {
         unsafe {
             instance
                 .enumerate_physical_devices()
-                .unwrap_or_else(|err| panic!("Failed to fetch the physical devices: {:?}\n", err))
+                .map_err(Error::vulkan("Failed to fetch the physical devices"))
         }
     }
 
     pub fn check_device_extension_support(
         instance: &Instance,
         physical_device: &vk::PhysicalDevice,
+        surface: &vk::SurfaceKHR,
     ) -> bool {
         let extension_properties = unsafe {
             instance
                 .enumerate_device_extension_properties(*physical_device)
-                .unwrap()
+                .unwrap_or_default()
         };
 
-        'cur_extension: for required_extension in DEVICE_EXTENSION_NAMES_RAW {
-            let required_extension_cstr = unsafe { CStr::from_ptr(required_extension) };
+        'cur_extension: for required_extension in get_device_extension_names(surface) {
+            let required_extension_cstr = unsafe { CStr::from_ptr(*required_extension) };
             for found_extension in &extension_properties {
                 let found_extension_cstr =
                     unsafe { CStr::from_ptr(found_extension.extension_name.as_ptr()) };
@@ -0,0 +1,195 @@
+use ash::vk::{
+    AccessFlags2, BufferImageCopy, BufferMemoryBarrier2, BufferUsageFlags, DependencyInfo,
+    Extent3D, ImageAspectFlags, ImageLayout, ImageSubresourceLayers, Offset3D, PipelineStageFlags2,
+    WHOLE_SIZE,
+};
+use half::f16;
+use image::{ImageFormat, ImageResult, Rgba32FImage, RgbaImage};
+use log::{debug, error};
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::application::{
+    error::{Error, Result},
+    vk_app::VulkanApp,
+};
+
+/// Pixels of the draw image copied to the host memory
+pub struct ImageReadback {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA values row by row, starting at the top left pixel
+    pub pixels: Vec<f32>,
+}
+
+impl ImageReadback {
+    /// Filmic tone mapping curve, see "ACES Filmic Tone Mapping Curve" (Narkowicz, 2015)
+    fn tone_map(value: f32) -> f32 {
+        let value = value.max(0.);
+        ((value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)).clamp(0., 1.)
+    }
+
+    /// Encodes a linear value in [0, 1] with the sRGB transfer function
+    fn linear_to_srgb(value: f32) -> f32 {
+        if value <= 0.0031308 {
+            value * 12.92
+        } else {
+            1.055 * value.powf(1. / 2.4) - 0.055
+        }
+    }
+
+    /// Writes the tone mapped colors as an 8 bit PNG, the alpha is kept linear
+    pub fn save_png(&self, path: &Path) -> ImageResult<()> {
+        let bytes = self
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+                let to_byte = |value: f32| (value * 255. + 0.5) as u8;
+                [
+                    to_byte(Self::linear_to_srgb(Self::tone_map(r))),
+                    to_byte(Self::linear_to_srgb(Self::tone_map(g))),
+                    to_byte(Self::linear_to_srgb(Self::tone_map(b))),
+                    to_byte(a.clamp(0., 1.)),
+                ]
+            })
+            .collect();
+        let image = RgbaImage::from_raw(self.width, self.height, bytes).unwrap();
+        image.save_with_format(path, ImageFormat::Png)
+    }
+
+    /// Writes the HDR values as an OpenEXR image
+    pub fn save_exr(&self, path: &Path) -> ImageResult<()> {
+        let image = Rgba32FImage::from_raw(self.width, self.height, self.pixels.clone()).unwrap();
+        image.save_with_format(path, ImageFormat::OpenExr)
+    }
+
+    /// Writes the image in the format given by the extension of the path, `.exr` or `.png`
+    pub fn save(&self, path: &Path) -> ImageResult<()> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("exr") => self.save_exr(path),
+            _ => self.save_png(path),
+        }
+    }
+}
+
+impl VulkanApp {
+    /// Copies the draw image to the host memory
+    /// The draw image must be in the transfer source layout
+    pub fn read_draw_image(&self) -> Result<ImageReadback> {
+        let width = self.draw_image.image_extent.width;
+        let height = self.draw_image.image_extent.height;
+        // the draw image is R16G16B16A16_SFLOAT
+        let nb_values = (width * height * 4) as usize;
+        let size = nb_values * std::mem::size_of::<f16>();
+
+        // wait for the frames in flight to finish writing the draw image
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .map_err(Error::vulkan("Failed to wait for the device"))?
+        };
+
+        let mut staging = self.create_buffer(
+            size,
+            BufferUsageFlags::TRANSFER_DST,
+            vk_mem::MemoryUsage::GpuToCpu,
+        )?;
+
+        self.immediate_submit(&|application, cmd| {
+            let image_subresource = ImageSubresourceLayers::default()
+                .aspect_mask(ImageAspectFlags::COLOR)
+                .mip_level(0)
+                .base_array_layer(0)
+                .layer_count(1);
+
+            // the buffer is tightly packed
+            let copy_regions = [BufferImageCopy::default()
+                .buffer_offset(0)
+                .buffer_row_length(0)
+                .buffer_image_height(0)
+                .image_subresource(image_subresource)
+                .image_offset(Offset3D::default())
+                .image_extent(Extent3D::default().width(width).height(height).depth(1))];
+
+            // make the copy visible to the host
+            let host_read_barrier = [BufferMemoryBarrier2::default()
+                .src_stage_mask(PipelineStageFlags2::TRANSFER)
+                .src_access_mask(AccessFlags2::TRANSFER_WRITE)
+                .dst_stage_mask(PipelineStageFlags2::HOST)
+                .dst_access_mask(AccessFlags2::HOST_READ)
+                .buffer(staging.buffer)
+                .offset(0)
+                .size(WHOLE_SIZE)];
+
+            let device = &application.device;
+            unsafe {
+                device.cmd_copy_image_to_buffer(
+                    cmd,
+                    application.draw_image.image,
+                    ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    staging.buffer,
+                    &copy_regions,
+                );
+                device.cmd_pipeline_barrier2(
+                    cmd,
+                    &DependencyInfo::default().buffer_memory_barriers(&host_read_barrier),
+                );
+            }
+        })?;
+
+        let pixels = {
+            let allocator = self.allocator.allocator.lock().unwrap();
+            allocator
+                .invalidate_allocation(&staging.allocation, 0, WHOLE_SIZE)
+                .map_err(Error::vulkan("Failed to invalidate the readback buffer"))?;
+            let data = allocator
+                .get_allocation_info(&staging.allocation)
+                .mapped_data as *const u16;
+
+            let values = unsafe { std::slice::from_raw_parts(data, nb_values) };
+            values
+                .iter()
+                .map(|&value| f16::from_bits(value).to_f32())
+                .collect()
+        };
+
+        VulkanApp::clear_buffer(&self.allocator.allocator.lock().unwrap(), &mut staging);
+
+        Ok(ImageReadback {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Saves the last rendered frame in the screenshot directory as PNG and OpenEXR
+    /// The draw image must be in the transfer source layout, as left by `draw`
+    pub fn save_screenshot(&self) -> Result<()> {
+        let readback = self.read_draw_image()?;
+
+        let directory = &self.app_params.screenshot_directory;
+        if let Err(err) = std::fs::create_dir_all(directory) {
+            error!(
+                "Failed to create the screenshot directory {:?}: {:?}",
+                directory, err
+            );
+            return Ok(());
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        for extension in ["png", "exr"] {
+            let path = directory.join(format!("screenshot-{}.{}", timestamp, extension));
+            match readback.save(&path) {
+                Ok(()) => debug!("Saved the screenshot {:?}", path),
+                Err(err) => error!("Failed to save the screenshot {:?}: {:?}", path, err),
+            }
+        }
+        Ok(())
+    }
+}
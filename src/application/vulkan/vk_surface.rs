@@ -1,4 +1,7 @@
-use crate::application::vk_app::VulkanApp;
+use crate::application::{
+    error::{Error, Result},
+    vk_app::VulkanApp,
+};
 
 use ash::{khr::surface, vk, Entry, Instance};
 
@@ -9,16 +12,26 @@ use winit::{
 
 impl VulkanApp {
     /// Init the surface
-    pub fn init_surface(entry: &Entry, instance: &Instance, window: &Window) -> vk::SurfaceKHR {
+    pub fn init_surface(
+        entry: &Entry,
+        instance: &Instance,
+        window: &Window,
+    ) -> Result<vk::SurfaceKHR> {
+        let display_handle = window
+            .display_handle()
+            .map_err(|err| Error::Window(err.to_string()))?;
+        let window_handle = window
+            .window_handle()
+            .map_err(|err| Error::Window(err.to_string()))?;
         unsafe {
             ash_window::create_surface(
                 entry,
                 instance,
-                window.display_handle().unwrap().as_raw(),
-                window.window_handle().unwrap().as_raw(),
+                display_handle.as_raw(),
+                window_handle.as_raw(),
                 None,
             )
-            .unwrap_or_else(|err| panic!("Failed to create the surface: {:?}\n", err))
+            .map_err(Error::vulkan("Failed to create the surface"))
         }
     }
 
@@ -27,6 +40,10 @@ impl VulkanApp {
     }
 
     pub fn clear_surface(&self) {
+        // there is no surface in headless mode
+        if self.surface == vk::SurfaceKHR::null() {
+            return;
+        }
         unsafe {
             self.surface_loader.destroy_surface(self.surface, None);
         }
@@ -7,37 +7,55 @@ use ash::{
     Device, Instance,
 };
 
-use crate::application::vk_app::{AppParameters, SwapChainSupportDetails, VulkanApp};
+use log::{debug, warn};
+use winit::window::Window;
+
+use crate::application::{
+    error::{Error, Result},
+    vk_app::{AppParameters, SwapChainSupportDetails, VulkanApp},
+};
+
+/// The swapchain, its images, their format and extent, and their views
+type Swapchain = (SwapchainKHR, Vec<Image>, Format, Extent2D, Vec<ImageView>);
+/// The swapchain loader followed by the content of `Swapchain`
+type SwapchainWithLoader = (
+    swapchain::Device,
+    SwapchainKHR,
+    Vec<Image>,
+    Format,
+    Extent2D,
+    Vec<ImageView>,
+);
 
 impl VulkanApp {
     pub fn query_swapchain_support(
         surface: &SurfaceKHR,
         surface_loader: &surface::Instance,
         physical_device: &PhysicalDevice,
-    ) -> SwapChainSupportDetails {
+    ) -> Result<SwapChainSupportDetails> {
         let surface_capabilities = unsafe {
             surface_loader
                 .get_physical_device_surface_capabilities(*physical_device, *surface)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to get the surface capabilities"))?
         };
 
         let surface_format = unsafe {
             surface_loader
                 .get_physical_device_surface_formats(*physical_device, *surface)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to get the surface formats"))?
         };
 
         let surface_present_modes = unsafe {
             surface_loader
                 .get_physical_device_surface_present_modes(*physical_device, *surface)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to get the surface present modes"))?
         };
 
-        SwapChainSupportDetails {
+        Ok(SwapChainSupportDetails {
             capabilities: surface_capabilities,
             formats: surface_format,
             present_modes: surface_present_modes,
-        }
+        })
     }
 
     fn choose_swapchain_format(available_formats: &[SurfaceFormatKHR]) -> SurfaceFormatKHR {
@@ -51,20 +69,27 @@ impl VulkanApp {
             .unwrap_or(available_formats[0])
     }
 
-    fn choose_present_mode(available_present_modes: &[PresentModeKHR]) -> PresentModeKHR {
-        available_present_modes
-            .iter()
-            .cloned()
-            // .find(|&present_mode| present_mode == vk::PresentModeKHR::MAILBOX)
-            .find(|&present_mode| present_mode == vk::PresentModeKHR::FIFO)
-            .unwrap_or(vk::PresentModeKHR::FIFO)
+    /// Uses the requested present mode, or FIFO which is always supported
+    fn choose_present_mode(
+        available_present_modes: &[PresentModeKHR],
+        requested_present_mode: PresentModeKHR,
+    ) -> PresentModeKHR {
+        if available_present_modes.contains(&requested_present_mode) {
+            requested_present_mode
+        } else {
+            warn!(
+                "The present mode {:?} is not supported, using FIFO instead",
+                requested_present_mode
+            );
+            vk::PresentModeKHR::FIFO
+        }
     }
 
     fn create_swapchain_image_views(
         swapchain_images: &[Image],
         swapchain_image_format: &Format,
         device: &Device,
-    ) -> Vec<ImageView> {
+    ) -> Result<Vec<ImageView>> {
         swapchain_images
             .iter()
             .map(|&image| {
@@ -88,7 +113,7 @@ impl VulkanApp {
                 unsafe {
                     device
                         .create_image_view(&create_view_info, None)
-                        .unwrap_or_else(|err| panic!("Failed to create image views: {:?}", err))
+                        .map_err(Error::vulkan("Failed to create image views"))
                 }
             })
             .collect()
@@ -101,16 +126,69 @@ impl VulkanApp {
         surface: &SurfaceKHR,
         surface_loader: &surface::Instance,
         physical_device: &PhysicalDevice,
-    ) -> (
-        swapchain::Device,
-        SwapchainKHR,
-        Vec<Image>,
-        Format,
-        Extent2D,
-        Vec<ImageView>,
-    ) {
+    ) -> Result<SwapchainWithLoader> {
+        let swapchain_loader = swapchain::Device::new(instance, device);
+
+        let (
+            swapchain,
+            swapchain_images,
+            swapchain_image_format,
+            surface_extent,
+            swapchain_image_views,
+        ) = Self::create_swapchain(
+            app_params,
+            &swapchain_loader,
+            device,
+            surface,
+            surface_loader,
+            physical_device,
+            SwapchainKHR::null(),
+        )?;
+
+        Ok((
+            swapchain_loader,
+            swapchain,
+            swapchain_images,
+            swapchain_image_format,
+            surface_extent,
+            swapchain_image_views,
+        ))
+    }
+
+    /// Placeholder swapchain for the headless mode, there is nothing to present
+    pub fn init_headless_swapchain(
+        app_params: &AppParameters,
+        instance: &Instance,
+        device: &Device,
+    ) -> Result<SwapchainWithLoader> {
+        let swapchain_loader = swapchain::Device::new(instance, device);
+        let extent = vk::Extent2D {
+            width: app_params.window_width as u32,
+            height: app_params.window_height as u32,
+        };
+
+        Ok((
+            swapchain_loader,
+            SwapchainKHR::null(),
+            Vec::new(),
+            Format::UNDEFINED,
+            extent,
+            Vec::new(),
+        ))
+    }
+
+    /// Creates a swapchain replacing `old_swapchain`, which is still destroyed by the caller
+    fn create_swapchain(
+        app_params: &AppParameters,
+        swapchain_loader: &swapchain::Device,
+        device: &Device,
+        surface: &SurfaceKHR,
+        surface_loader: &surface::Instance,
+        physical_device: &PhysicalDevice,
+        old_swapchain: SwapchainKHR,
+    ) -> Result<Swapchain> {
         let swapchain_support =
-            Self::query_swapchain_support(surface, surface_loader, physical_device);
+            Self::query_swapchain_support(surface, surface_loader, physical_device)?;
         let mut desired_image_count = swapchain_support.capabilities.min_image_count + 1;
         if swapchain_support.capabilities.max_image_count > 0
             && desired_image_count > swapchain_support.capabilities.max_image_count
@@ -119,8 +197,14 @@ impl VulkanApp {
         }
         let surface_extent = match swapchain_support.capabilities.current_extent.width {
             u32::MAX => vk::Extent2D {
-                width: app_params.window_width as u32,
-                height: app_params.window_height as u32,
+                width: (app_params.window_width as u32).clamp(
+                    swapchain_support.capabilities.min_image_extent.width,
+                    swapchain_support.capabilities.max_image_extent.width,
+                ),
+                height: (app_params.window_height as u32).clamp(
+                    swapchain_support.capabilities.min_image_extent.height,
+                    swapchain_support.capabilities.max_image_extent.height,
+                ),
             },
             _ => swapchain_support.capabilities.current_extent,
         };
@@ -135,9 +219,8 @@ impl VulkanApp {
         };
 
         let surface_format = Self::choose_swapchain_format(&swapchain_support.formats);
-        let surface_present_mode = Self::choose_present_mode(&swapchain_support.present_modes);
-
-        let swapchain_loader = swapchain::Device::new(instance, device);
+        let surface_present_mode =
+            Self::choose_present_mode(&swapchain_support.present_modes, app_params.present_mode);
 
         let swapchain_create_info = vk::SwapchainCreateInfoKHR::default()
             .surface(*surface)
@@ -151,34 +234,115 @@ impl VulkanApp {
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(surface_present_mode)
             .clipped(true)
-            .image_array_layers(1);
+            .image_array_layers(1)
+            .old_swapchain(old_swapchain);
 
         let swapchain = unsafe {
             swapchain_loader
                 .create_swapchain(&swapchain_create_info, None)
-                .unwrap_or_else(|err| panic!("Failed to initialized the swapchain: {:?}", err))
+                .map_err(Error::vulkan("Failed to initialize the swapchain"))?
         };
 
         let swapchain_images = unsafe {
             swapchain_loader
                 .get_swapchain_images(swapchain)
-                .unwrap_or_else(|err| panic!("Failed to create images: {:?}", err))
+                .map_err(Error::vulkan("Failed to get the swapchain images"))?
         };
 
         let swapchain_image_views =
-            Self::create_swapchain_image_views(&swapchain_images, &surface_format.format, device);
+            Self::create_swapchain_image_views(&swapchain_images, &surface_format.format, device)?;
 
-        (
-            swapchain_loader,
+        Ok((
             swapchain,
             swapchain_images,
             surface_format.format,
             surface_extent,
             swapchain_image_views,
-        )
+        ))
+    }
+
+    /// Recreates the swapchain and the draw images with the current size of the window
+    /// The descriptors referencing the draw images are updated by the pipelines
+    pub fn recreate_swapchain(&mut self, window: &Window) -> Result<()> {
+        let window_size = window.inner_size();
+        debug!(
+            "Recreate Swapchain ({}x{})...",
+            window_size.width, window_size.height
+        );
+        self.app_params.window_width = window_size.width as i32;
+        self.app_params.window_height = window_size.height as i32;
+
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .map_err(Error::vulkan("Failed to wait for the device"))?
+        };
+
+        // the previous swapchain and images are only destroyed once their replacements exist,
+        // on an error they are still destroyed by the drop
+        let (mut draw_image, mut accumulation_image) = {
+            let allocator = self.allocator.allocator.lock().unwrap();
+            Self::init_images(&self.app_params, &self.device, &allocator)?
+        };
+        let swapchain = Self::create_swapchain(
+            &self.app_params,
+            &self.swapchain_loader,
+            &self.device,
+            &self.surface,
+            &self.surface_loader,
+            &self.physical_device,
+            self.swapchain,
+        );
+        let (
+            swapchain,
+            swapchain_images,
+            swapchain_image_format,
+            swapchain_extent,
+            swapchain_image_views,
+        ) = match swapchain {
+            Ok(swapchain) => swapchain,
+            Err(err) => {
+                let allocator = self.allocator.allocator.lock().unwrap();
+                Self::clear_image(&self.device, &allocator, &mut draw_image);
+                Self::clear_image(&self.device, &allocator, &mut accumulation_image);
+                return Err(err);
+            }
+        };
+
+        // also destroys the swapchain image views
+        self.clear_images();
+        self.clear_swapchain();
+
+        self.swapchain = swapchain;
+        self.swapchain_images = swapchain_images;
+        self.swapchain_image_format = swapchain_image_format;
+        self.swapchain_extent = swapchain_extent;
+        self.swapchain_image_views = swapchain_image_views;
+        self.draw_image = draw_image;
+        self.accumulation_image = accumulation_image;
+
+        // Take the pipelines out of self temporarily
+        let mut pipelines = std::mem::take(&mut self.pipelines);
+        let update_result = pipelines
+            .iter_mut()
+            .try_for_each(|pipeline| pipeline.update_image_descriptors(self));
+        self.pipelines = pipelines;
+        update_result?;
+
+        if let Some(camera) = self.scene.camera.as_mut() {
+            camera.aspect_ratio = window_size.width as f32 / window_size.height as f32;
+        }
+        self.reset_accumulation();
+        self.resize_requested = false;
+        debug!("Ok\n");
+        Ok(())
     }
 
     pub fn clear_swapchain(&self) {
+        // there is no swapchain in headless mode
+        if self.swapchain == SwapchainKHR::null() {
+            return;
+        }
         unsafe {
             self.swapchain_loader
                 .destroy_swapchain(self.swapchain, None);
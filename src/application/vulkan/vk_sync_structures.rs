@@ -3,28 +3,39 @@ use ash::{
     Device,
 };
 
-use crate::application::vk_app::{FrameData, VulkanApp, FRAME_OVERLAP};
+use crate::application::{
+    error::{Error, Result},
+    vk_app::{FrameData, VulkanApp, FRAME_OVERLAP},
+};
 
 impl VulkanApp {
-    pub fn init_sync_structuress(device: &Device, frames: &mut [FrameData; FRAME_OVERLAP]) {
+    pub fn init_sync_structuress(
+        device: &Device,
+        frames: &mut [FrameData; FRAME_OVERLAP],
+    ) -> Result<()> {
         let fence_create_info = FenceCreateInfo::default().flags(FenceCreateFlags::SIGNALED);
         let semaphore_create_info = SemaphoreCreateInfo::default();
 
-        frames.iter_mut().for_each(|frame| {
-            let render_fence = unsafe { device.create_fence(&fence_create_info, None).unwrap() };
+        for frame in frames.iter_mut() {
+            let render_fence = unsafe {
+                device
+                    .create_fence(&fence_create_info, None)
+                    .map_err(Error::vulkan("Failed to create a fence"))?
+            };
             let render_semaphore = unsafe {
                 device
                     .create_semaphore(&semaphore_create_info, None)
-                    .unwrap()
+                    .map_err(Error::vulkan("Failed to create a semaphore"))?
             };
             let swapchain_semaphore = unsafe {
                 device
                     .create_semaphore(&semaphore_create_info, None)
-                    .unwrap()
+                    .map_err(Error::vulkan("Failed to create a semaphore"))?
             };
             frame.render_fence = render_fence;
             frame.render_semaphore = render_semaphore;
             frame.swapchain_semaphore = swapchain_semaphore;
-        });
+        }
+        Ok(())
     }
 }
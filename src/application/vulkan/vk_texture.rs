@@ -0,0 +1,205 @@
+use ash::{
+    vk::{
+        self, AccessFlags2, BlitImageInfo2, BorderColor, BufferImageCopy, BufferUsageFlags,
+        CommandBuffer, CompareOp, DependencyInfo, Extent3D, Filter, Image, ImageAspectFlags,
+        ImageBlit2, ImageLayout, ImageMemoryBarrier2, ImageSubresourceLayers,
+        ImageSubresourceRange, ImageUsageFlags, Offset3D, PipelineStageFlags2, Sampler,
+        SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode,
+    },
+    Device,
+};
+
+use crate::application::{
+    error::{Error, Result},
+    scenes::texture::Texture,
+    vk_app::{AllocatedImage, VulkanApp},
+};
+
+impl VulkanApp {
+    /// Copies the texels to a new sampled image and generates its mipmaps
+    /// The image is left in the `SHADER_READ_ONLY_OPTIMAL` layout
+    pub fn upload_texture(&self, texture: &Texture) -> Result<AllocatedImage> {
+        let extent = Extent3D::default()
+            .width(texture.width)
+            .height(texture.height)
+            .depth(1);
+        let mip_levels = texture.get_mip_levels();
+
+        // the mip levels are blitted from the previous one
+        let mut image = {
+            let allocator = self.allocator.allocator.lock().unwrap();
+            Self::create_image(
+                &self.device,
+                &allocator,
+                extent,
+                texture.format,
+                ImageUsageFlags::SAMPLED
+                    | ImageUsageFlags::TRANSFER_SRC
+                    | ImageUsageFlags::TRANSFER_DST,
+                mip_levels,
+            )?
+        };
+
+        let size = texture.texels.len();
+        let mut staging = self.create_buffer(
+            size,
+            BufferUsageFlags::TRANSFER_SRC,
+            vk_mem::MemoryUsage::CpuOnly,
+        )?;
+
+        // Lock the allocator and map the staging buffer
+        {
+            let allocator = self.allocator.allocator.lock().unwrap();
+            let data = allocator
+                .get_allocation_info(&staging.allocation)
+                .mapped_data as *mut u8;
+
+            // copy texels
+            unsafe {
+                let data_slice = std::slice::from_raw_parts_mut(data, size);
+                data_slice.copy_from_slice(&texture.texels);
+            };
+        } // The lock on the allocator is released here
+
+        let upload_result = self.immediate_submit(&|application, cmd| {
+            let device = &application.device;
+            Self::transition_image(
+                device,
+                &cmd,
+                &image.image,
+                &ImageLayout::UNDEFINED,
+                &ImageLayout::TRANSFER_DST_OPTIMAL,
+            );
+
+            let texels_copy = [BufferImageCopy::default()
+                .image_subresource(get_mip_level_layers(0))
+                .image_extent(extent)];
+            unsafe {
+                device.cmd_copy_buffer_to_image(
+                    cmd,
+                    staging.buffer,
+                    image.image,
+                    ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &texels_copy,
+                );
+            }
+
+            Self::generate_mipmaps(device, &cmd, &image.image, extent, mip_levels);
+
+            // every level is a blit source after the mipmaps generation
+            Self::transition_image(
+                device,
+                &cmd,
+                &image.image,
+                &ImageLayout::TRANSFER_SRC_OPTIMAL,
+                &ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        });
+
+        // Lock the allocator again to destroy the staging buffer
+        let allocator = self.allocator.allocator.lock().unwrap();
+        VulkanApp::clear_buffer(&allocator, &mut staging);
+
+        match upload_result {
+            Ok(()) => Ok(image),
+            Err(err) => {
+                Self::clear_image(&self.device, &allocator, &mut image);
+                Err(err)
+            }
+        }
+    }
+
+    /// Records the blits halving each mip level into the next one
+    /// The level 0 must be in the `TRANSFER_DST_OPTIMAL` layout, every level ends in `TRANSFER_SRC_OPTIMAL`
+    fn generate_mipmaps(
+        device: &Device,
+        command_buffer: &CommandBuffer,
+        image: &Image,
+        extent: Extent3D,
+        mip_levels: u32,
+    ) {
+        let get_mip_offset = |mip_level: u32| {
+            Offset3D::default()
+                .x((extent.width >> mip_level).max(1) as i32)
+                .y((extent.height >> mip_level).max(1) as i32)
+                .z(1)
+        };
+
+        for mip_level in 0..mip_levels {
+            // the previous writes of the level are done before reading it
+            let level_barrier = [ImageMemoryBarrier2::default()
+                .src_stage_mask(PipelineStageFlags2::TRANSFER)
+                .src_access_mask(AccessFlags2::TRANSFER_WRITE)
+                .dst_stage_mask(PipelineStageFlags2::TRANSFER)
+                .dst_access_mask(AccessFlags2::TRANSFER_READ)
+                .old_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .subresource_range(
+                    ImageSubresourceRange::default()
+                        .aspect_mask(ImageAspectFlags::COLOR)
+                        .base_mip_level(mip_level)
+                        .level_count(1)
+                        .base_array_layer(0)
+                        .layer_count(1),
+                )
+                .image(*image)];
+            unsafe {
+                device.cmd_pipeline_barrier2(
+                    *command_buffer,
+                    &DependencyInfo::default().image_memory_barriers(&level_barrier),
+                );
+            }
+
+            if mip_level + 1 == mip_levels {
+                break;
+            }
+
+            let blit_regions = [ImageBlit2::default()
+                .src_offsets([Offset3D::default(), get_mip_offset(mip_level)])
+                .dst_offsets([Offset3D::default(), get_mip_offset(mip_level + 1)])
+                .src_subresource(get_mip_level_layers(mip_level))
+                .dst_subresource(get_mip_level_layers(mip_level + 1))];
+
+            let blit_image_info = BlitImageInfo2::default()
+                .src_image(*image)
+                .src_image_layout(ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .dst_image(*image)
+                .dst_image_layout(ImageLayout::TRANSFER_DST_OPTIMAL)
+                .filter(Filter::LINEAR)
+                .regions(&blit_regions);
+
+            unsafe {
+                device.cmd_blit_image2(*command_buffer, &blit_image_info);
+            }
+        }
+    }
+
+    /// Trilinear sampler repeating the textures, shared by every texture of the scene
+    pub fn create_texture_sampler(device: &Device) -> Result<Sampler> {
+        let sampler_info = SamplerCreateInfo::default()
+            .mag_filter(Filter::LINEAR)
+            .min_filter(Filter::LINEAR)
+            .mipmap_mode(SamplerMipmapMode::LINEAR)
+            .address_mode_u(SamplerAddressMode::REPEAT)
+            .address_mode_v(SamplerAddressMode::REPEAT)
+            .address_mode_w(SamplerAddressMode::REPEAT)
+            .compare_op(CompareOp::NEVER)
+            .min_lod(0.)
+            .max_lod(vk::LOD_CLAMP_NONE)
+            .border_color(BorderColor::FLOAT_OPAQUE_BLACK);
+
+        unsafe {
+            device
+                .create_sampler(&sampler_info, None)
+                .map_err(Error::vulkan("Failed to create the texture sampler"))
+        }
+    }
+}
+
+fn get_mip_level_layers(mip_level: u32) -> ImageSubresourceLayers {
+    ImageSubresourceLayers::default()
+        .aspect_mask(ImageAspectFlags::COLOR)
+        .base_array_layer(0)
+        .layer_count(1)
+        .mip_level(mip_level)
+}
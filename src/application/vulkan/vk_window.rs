@@ -1,4 +1,7 @@
-use crate::application::vk_app::{AppParameters, VulkanApp};
+use crate::application::{
+    error::{Error, Result},
+    vk_app::{AppParameters, VulkanApp},
+};
 
 use winit::{
     dpi::{LogicalSize, PhysicalSize, Position},
@@ -8,14 +11,15 @@ use winit::{
 
 impl VulkanApp {
     /// Initializes the event loop for handling window events.
-    pub fn init_event_loop() -> EventLoop<()> {
-        EventLoop::new()
-            .unwrap_or_else(|err| panic!("Failed to create the event loop: {:?}\n", err))
+    pub fn init_event_loop() -> Result<EventLoop<()>> {
+        EventLoop::new().map_err(|err| Error::Window(err.to_string()))
     }
 
     /// Initializes the window with given application parameters and event loop.
-    pub fn init_window(app_params: &AppParameters, event_loop: &EventLoop<()>) -> Window {
-        let primary_monitor = event_loop.primary_monitor().unwrap();
+    pub fn init_window(app_params: &AppParameters, event_loop: &EventLoop<()>) -> Result<Window> {
+        let primary_monitor = event_loop
+            .primary_monitor()
+            .ok_or_else(|| Error::Window("no primary monitor".to_string()))?;
         let scale_factor = primary_monitor.scale_factor();
 
         // Desired window size in logical units
@@ -35,10 +39,10 @@ impl VulkanApp {
 
         WindowBuilder::new()
             .with_title(&app_params.name)
-            .with_resizable(false)
+            .with_resizable(true)
             .with_inner_size(window_size)
             .with_position(position)
             .build(event_loop)
-            .unwrap_or_else(|err| panic!("Failed to initialize the window: {:?}\n", err))
+            .map_err(|err| Error::Window(err.to_string()))
     }
 }
@@ -0,0 +1,112 @@
+use std::path::PathBuf;
+
+use ash::vk::PresentModeKHR;
+use clap::{Parser, ValueEnum};
+
+use crate::application::vk_app::{AppParameters, DeviceSelection};
+
+/// Progressive path tracer running on Vulkan
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Width of the window, or of the image in headless mode
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub width: Option<i32>,
+
+    /// Height of the window, or of the image in headless mode
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    pub height: Option<i32>,
+
+    /// Scene to load: `.obj`, `.gltf`, `.glb` or a `.ron` scene description
+    #[arg(long)]
+    pub scene: Option<PathBuf>,
+
+    /// Physical device to use, by index or by a part of its name
+    #[arg(long)]
+    pub device: Option<String>,
+
+    /// Presentation mode of the swapchain
+    #[arg(long, value_enum)]
+    pub present_mode: Option<PresentMode>,
+
+    /// Number of samples traced per pixel every frame
+    #[arg(long)]
+    pub spp: Option<u32>,
+
+    /// Renders without a window and writes the image, `.png` or `.exr`
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// Number of frames accumulated without a window
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub frames: Option<u32>,
+
+    /// Directory of compiled shaders overriding the embedded ones, used by the hot reload
+    #[arg(long)]
+    pub shader_dir: Option<PathBuf>,
+
+    /// Directory of the Slang sources recompiled into the shader directory when they change
+    #[arg(long)]
+    pub shader_source_dir: Option<PathBuf>,
+
+    /// Prints the physical devices and why they are rejected, then exits
+    #[arg(long)]
+    pub list_devices: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PresentMode {
+    Immediate,
+    Mailbox,
+    Fifo,
+    FifoRelaxed,
+}
+
+impl From<PresentMode> for PresentModeKHR {
+    fn from(present_mode: PresentMode) -> Self {
+        match present_mode {
+            PresentMode::Immediate => PresentModeKHR::IMMEDIATE,
+            PresentMode::Mailbox => PresentModeKHR::MAILBOX,
+            PresentMode::Fifo => PresentModeKHR::FIFO,
+            PresentMode::FifoRelaxed => PresentModeKHR::FIFO_RELAXED,
+        }
+    }
+}
+
+impl Cli {
+    /// Overrides the default parameters with the given arguments
+    /// The headless mode is used as soon as an output or a number of frames is given
+    pub fn get_app_parameters(&self) -> AppParameters {
+        let mut app_params = AppParameters::default();
+        if let Some(width) = self.width {
+            app_params.window_width = width;
+        }
+        if let Some(height) = self.height {
+            app_params.window_height = height;
+        }
+        app_params.scene_file = self.scene.clone();
+        if let Some(device) = &self.device {
+            app_params.device_selection = match device.parse() {
+                Ok(index) => DeviceSelection::Index(index),
+                Err(_) => DeviceSelection::Name(device.clone()),
+            };
+        }
+        if let Some(present_mode) = self.present_mode {
+            app_params.present_mode = present_mode.into();
+        }
+        if let Some(spp) = self.spp {
+            app_params.samples_per_pixel = spp;
+        }
+        if let Some(shader_dir) = &self.shader_dir {
+            app_params.shader_directory = Some(shader_dir.clone());
+        }
+        if let Some(shader_source_dir) = &self.shader_source_dir {
+            app_params.shader_source_directory = Some(shader_source_dir.clone());
+        }
+        if self.output.is_some() || self.frames.is_some() {
+            app_params.headless_frames = Some(self.frames.unwrap_or(1));
+            app_params.headless_output = self.output.clone();
+        }
+        app_params
+    }
+}
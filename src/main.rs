@@ -1,9 +1,24 @@
 use application::vk_app::VulkanApp;
+use clap::Parser;
+use cli::Cli;
+use log::error;
 
 pub mod application;
+mod cli;
 
 fn main() {
     env_logger::init();
-    // run the app
-    VulkanApp::run(Default::default());
+    let cli = Cli::parse();
+    let app_params = cli.get_app_parameters();
+
+    // list the physical devices or run the app
+    let result = if cli.list_devices {
+        VulkanApp::list_devices(&app_params)
+    } else {
+        VulkanApp::run(app_params)
+    };
+    if let Err(err) = result {
+        error!("{}", err);
+        std::process::exit(1);
+    }
 }